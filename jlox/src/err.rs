@@ -1,8 +1,8 @@
-use std::option::NoneError;
-
 use crate::{
+  lit::Lit,
   pos::Pos,
   scanner::Token,
+  scanner::TokenType,
 };
 
 #[derive(Clone)]
@@ -22,8 +22,15 @@ pub enum LoxError {
   Other {
     message: String
   },
+  // not an actual error, unwinds out of a function body up to its call
+  Return {
+    value: Lit,
+  },
 }
 
+// what `?` on a None turns into, `Option::ok_or(NoneError)?`
+pub struct NoneError;
+
 pub type LoxResult<T> = Result<T, LoxError>;
 
 impl LoxError {
@@ -41,6 +48,34 @@ impl LoxError {
   }
 }
 
+impl ::std::fmt::Display for LoxError {
+  fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+    match self {
+      LoxError::ParseError { token, message } => {
+        let cause = if token.ty == TokenType::EOF {
+          " at end".to_string()
+        } else {
+          format!(" at '{}'", token.raw)
+        };
+
+        write!(f, "[Line: {}] Error{}: {}", token.pos, cause, message)
+      }
+      LoxError::LexError { pos, message } => {
+        write!(f, "[Line: {}] Error: {}", pos, message)
+      }
+      LoxError::Other { message } => {
+        write!(f, "[??] Unexpected Error: {}", message)
+      }
+      LoxError::RuntimeError { token, message } => {
+        write!(f, "[Line: {}] RuntimeError: {}", token.pos, message)
+      }
+      LoxError::Return { .. } => {
+        write!(f, "[??] Unexpected Error: return outside of a function")
+      }
+    }
+  }
+}
+
 impl From<NoneError> for LoxError {
   fn from(_: NoneError) -> Self {
     LoxError::other(format!("Unexpected None"))
@@ -1,3 +1,5 @@
+use std::rc::Rc;
+
 use crate::{
   lit::Lit,
   scanner::Token,
@@ -13,8 +15,12 @@ pub enum Expr {
   Binary { left: Box<Expr>, op: Token, right: Box<Expr> },
   Call { callee: Box<Expr>, paren: Token, arguments: Vec<Expr> },
   Grouping { expr: Box<Expr> },
+  Index { object: Box<Expr>, bracket: Token, index: Box<Expr> },
+  Interpolation { parts: Vec<Expr> },
   Literal { lit: Lit },
   Logical { left: Box<Expr>, op: Token, right: Box<Expr> },
+  // only made by `return a, b;`
+  Tuple { elements: Vec<Expr> },
   Unary { op: Token, right: Box<Expr> },
   Variable { name: Token },
 }
@@ -24,6 +30,22 @@ impl Expr {
     visitor.visit(self)
   }
 
+  pub fn name(&self) -> &'static str {
+    match self {
+      Expr::Assign { .. } => "Expr::Assign",
+      Expr::Binary { .. } => "Expr::Binary",
+      Expr::Call { .. } => "Expr::Call",
+      Expr::Grouping { .. } => "Expr::Grouping",
+      Expr::Index { .. } => "Expr::Index",
+      Expr::Interpolation { .. } => "Expr::Interpolation",
+      Expr::Literal { .. } => "Expr::Literal",
+      Expr::Logical { .. } => "Expr::Logical",
+      Expr::Tuple { .. } => "Expr::Tuple",
+      Expr::Unary { .. } => "Expr::Unary",
+      Expr::Variable { .. } => "Expr::Variable",
+    }
+  }
+
   pub fn assign(name: Token, value: Expr) -> Self {
     Expr::Assign { name, value: Box::new(value) }
   }
@@ -40,6 +62,14 @@ impl Expr {
     Expr::Grouping { expr: Box::new(expr) }
   }
 
+  pub fn index(object: Expr, bracket: Token, index: Expr) -> Self {
+    Expr::Index { object: Box::new(object), bracket, index: Box::new(index) }
+  }
+
+  pub fn interpolation(parts: Vec<Expr>) -> Self {
+    Expr::Interpolation { parts }
+  }
+
   pub fn lit(lit: Lit) -> Self {
     Expr::Literal { lit }
   }
@@ -48,6 +78,10 @@ impl Expr {
     Expr::Logical { left: Box::new(left), op, right: Box::new(right) }
   }
 
+  pub fn tuple(elements: Vec<Expr>) -> Self {
+    Expr::Tuple { elements }
+  }
+
   pub fn unary(op: Token, right: Expr) -> Self {
     Expr::Unary { op, right: Box::new(right) }
   }
@@ -63,11 +97,16 @@ pub trait StmtVisitor<T> {
 
 #[derive(Clone, PartialEq)]
 pub enum Stmt {
+  Assert { keyword: Token, condition: Expr, message: Option<Expr> },
   Block { statements: Vec<Stmt> },
+  // `var (a, b) = init;`
+  Destructure { paren: Token, names: Vec<Token>, init: Expr },
   Expression { expr: Expr },
-  Function { name: Token, params: Vec<Token>, body: Vec<Stmt> },
+  // params and body are shared with every function value created from the declaration
+  Function { name: Token, params: Rc<Vec<Token>>, body: Rc<Vec<Stmt>>, attributes: Vec<Token> },
   If { condition: Expr, then_branch: Box<Stmt>, else_branch: Option<Box<Stmt>> },
   Print { expr: Expr },
+  Return { keyword: Token, value: Option<Expr> },
   Var { name: Token, init: Option<Expr> },
   While { condition: Expr, body: Box<Stmt> },
 }
@@ -77,16 +116,39 @@ impl Stmt {
     visitor.visit(self)
   }
 
+  pub fn name(&self) -> &'static str {
+    match self {
+      Stmt::Assert { .. } => "Stmt::Assert",
+      Stmt::Block { .. } => "Stmt::Block",
+      Stmt::Destructure { .. } => "Stmt::Destructure",
+      Stmt::Expression { .. } => "Stmt::Expression",
+      Stmt::Function { .. } => "Stmt::Function",
+      Stmt::If { .. } => "Stmt::If",
+      Stmt::Print { .. } => "Stmt::Print",
+      Stmt::Return { .. } => "Stmt::Return",
+      Stmt::Var { .. } => "Stmt::Var",
+      Stmt::While { .. } => "Stmt::While",
+    }
+  }
+
+  pub fn assert(keyword: Token, condition: Expr, message: Option<Expr>) -> Self {
+    Stmt::Assert { keyword, condition, message }
+  }
+
   pub fn block(statements: Vec<Stmt>) -> Self {
     Stmt::Block { statements }
   }
 
+  pub fn destructure(paren: Token, names: Vec<Token>, init: Expr) -> Self {
+    Stmt::Destructure { paren, names, init }
+  }
+
   pub fn expression(expr: Expr) -> Self {
     Stmt::Expression { expr }
   }
 
-  pub fn function(name: Token, params: Vec<Token>, body: Vec<Stmt>) -> Self {
-    Stmt::Function { name, params, body }
+  pub fn function(name: Token, params: Vec<Token>, body: Vec<Stmt>, attributes: Vec<Token>) -> Self {
+    Stmt::Function { name, params: Rc::new(params), body: Rc::new(body), attributes }
   }
 
   pub fn if_stmt(condition: Expr, then_branch: Stmt, else_branch: Option<Stmt>) -> Self {
@@ -97,6 +159,10 @@ impl Stmt {
     Stmt::Print { expr }
   }
 
+  pub fn return_stmt(keyword: Token, value: Option<Expr>) -> Self {
+    Stmt::Return { keyword, value }
+  }
+
   pub fn var(name: Token, init: Option<Expr>) -> Self {
     Stmt::Var { name, init }
   }
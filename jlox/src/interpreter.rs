@@ -10,47 +10,57 @@ use crate::{
   lit::Lit,
   expr::Stmt,
   err::LoxResult,
-  lit::Function
+  err::NoneError,
+  lit::Function,
+  lit::NativeFn,
+  parser::Parser,
+  prelude,
+  profiler::Profiler,
+  recorder::Recorder,
+  scanner::Scanner,
+  stack,
 };
 use std::{
   mem,
+  rc::Rc,
+  cell::RefCell,
+  time::Instant,
   collections::HashMap,
 };
 
 pub struct Environment {
   values: HashMap<String, Lit>,
-  enclosing: Option<Box<Environment>>,
+  enclosing: Option<Rc<RefCell<Environment>>>,
 }
 
 impl Environment {
-  pub fn new(enclosing: Option<Environment>) -> Self {
+  pub fn new(enclosing: Option<Rc<RefCell<Environment>>>) -> Self {
     Environment {
       values: HashMap::new(),
-      enclosing: enclosing.map(Box::new),
+      enclosing,
     }
   }
 
-  pub fn define(&mut self, name: String, value: Lit) {
-    self.values.insert(name, value);
+  // both return the value that got replaced
+  pub fn define(&mut self, name: String, value: Lit) -> Option<Lit> {
+    self.values.insert(name, value)
   }
 
-  pub fn assign(&mut self, name: &Token, value: Lit) -> LoxResult<()> {
+  pub fn assign(&mut self, name: &Token, value: Lit) -> LoxResult<Lit> {
     if let Some(val) = self.values.get_mut(&name.raw) {
-      *val = value;
-    } else if let Some(ref mut enclosing) = self.enclosing {
-      enclosing.assign(name, value)?;
+      Ok(mem::replace(val, value))
+    } else if let Some(ref enclosing) = self.enclosing {
+      enclosing.borrow_mut().assign(name, value)
     } else {
-      return Err(LoxError::runtime(name.clone(), format!("Undefined variable '{}'.", &name.raw)));
+      Err(LoxError::runtime(name.clone(), format!("Undefined variable '{}'.", &name.raw)))
     }
-
-    Ok(())
   }
 
   pub fn get(&self, name: &Token) -> LoxResult<Lit> {
     if let Some(lit) = self.values.get(&name.raw) {
       Ok(lit.clone())
     } else if let Some(ref enclosing) = self.enclosing {
-      enclosing.get(name)
+      enclosing.borrow().get(name)
     } else {
       Err(LoxError::runtime(
         name.clone(),
@@ -58,53 +68,257 @@ impl Environment {
       ))
     }
   }
+}
 
-  pub fn set_enclosing(&mut self, enclosing: Environment) {
-    self.enclosing = Some(Box::new(enclosing));
-  }
+const MAX_CALL_DEPTH: usize = 255;
+// how much native stack running a program may take, this holds on a 2MB test thread but a debug
+// build spends a few dozen KB per Lox call so it only gets around 25 calls deep.
+// Release builds get several times further, hosts on a bigger stack can raise it
+const MAX_STACK: usize = 1024 * 1024;
 
-  pub fn take_enclosing(&mut self) -> Option<Environment> {
-    let mut enclosing = None;
-    mem::swap(&mut enclosing, &mut self.enclosing);
-    enclosing.map(|env| *env)
-  }
+pub struct Interpreter {
+  environment: Rc<RefCell<Environment>>,
+  // read undefined variables as nil instead of erroring
+  lenient_undefined: bool,
+  // numeric operators with a nil operand yield nil instead of erroring, like SQL's NULL
+  lenient_arithmetic: bool,
+  profiler: Option<Profiler>,
+  recorder: Option<Recorder>,
+  // variables the env() native reads, the process environment when unset
+  host_env: Option<HashMap<String, String>>,
+  // names of the functions currently being called, innermost last
+  call_stack: Vec<String>,
+  max_call_depth: usize,
+  // the stack where the host called in and how far past it we may go,
+  // the call depth alone says little with how much heavier some code is than other
+  stack_base: Option<usize>,
+  max_stack: usize,
+  // skip `assert` statements entirely, without evaluating them
+  strip_asserts: bool,
+  // what `print` writes when the host captures it instead of stdout
+  output: Option<String>,
 }
 
-pub struct Interpreter {
-  environment: Environment,
+impl Default for Interpreter {
+  fn default() -> Self {
+    Interpreter::new()
+  }
 }
 
 impl Interpreter {
   pub fn new() -> Self {
-    let mut environment = Environment::new(None);
+    let mut interpreter = Interpreter {
+      environment: Rc::new(RefCell::new(Environment::new(None))),
+      lenient_undefined: false,
+      lenient_arithmetic: false,
+      profiler: None,
+      recorder: None,
+      host_env: None,
+      call_stack: Vec::new(),
+      max_call_depth: MAX_CALL_DEPTH,
+      stack_base: None,
+      max_stack: MAX_STACK,
+      strip_asserts: false,
+      output: None,
+    };
+
+    prelude::define(&mut interpreter);
+
+    interpreter
+  }
+
+  // an interpreter with host provided values defined next to the prelude,
+  // they shadow prelude names of the same name
+  pub fn with_globals(globals: HashMap<String, Lit>) -> Self {
+    let interpreter = Interpreter::new();
+    for (name, value) in globals {
+      interpreter.environment.borrow_mut().define(name, value);
+    }
+    interpreter
+  }
+
+  pub fn set_strip_asserts(&mut self, strip: bool) {
+    self.strip_asserts = strip;
+  }
+
+  pub fn set_lenient_undefined(&mut self, lenient: bool) {
+    self.lenient_undefined = lenient;
+  }
+
+  pub fn set_lenient_arithmetic(&mut self, lenient: bool) {
+    self.lenient_arithmetic = lenient;
+  }
+
+  pub fn enable_profiler(&mut self) {
+    self.profiler = Some(Profiler::new());
+  }
+
+  pub fn profiler(&self) -> Option<&Profiler> {
+    self.profiler.as_ref()
+  }
+
+  pub fn enable_recorder(&mut self) {
+    self.recorder = Some(Recorder::new());
+  }
+
+  pub fn recorder(&self) -> Option<&Recorder> {
+    self.recorder.as_ref()
+  }
+
+  pub fn capture_output(&mut self) {
+    self.output = Some(String::new());
+  }
+
+  // what got printed since the last call, empty if output isn't captured
+  pub fn take_output(&mut self) -> String {
+    self.output.as_mut().map_or(String::new(), mem::take)
+  }
+
+  pub fn print(&mut self, line: &str) {
+    match self.output {
+      Some(ref mut output) => {
+        output.push_str(line);
+        output.push('\n');
+      }
+      None => println!("{}", line),
+    }
+  }
+
+  // defines a variable declared in the source, so the recorder gets to see it
+  pub fn define_var(&mut self, environment: &mut Environment, name: &Token, value: Lit) {
+    match self.recorder {
+      Some(ref mut recorder) => {
+        let old = environment.define(name.raw.clone(), value.clone());
+        recorder.record(name, old, value);
+      }
+      None => {
+        environment.define(name.raw.clone(), value);
+      }
+    }
+  }
+
+  pub fn set_max_call_depth(&mut self, max: usize) {
+    self.max_call_depth = max;
+  }
+
+  pub fn set_max_stack(&mut self, bytes: usize) {
+    self.max_stack = bytes;
+  }
+
+  // counts the stack budget from here, unless the host already called in further up
+  fn budgeted<T>(&mut self, run: impl FnOnce(&mut Self) -> LoxResult<T>) -> LoxResult<T> {
+    if self.stack_base.is_some() {
+      return run(self);
+    }
+
+    self.stack_base = Some(stack::position());
+    let res = run(self);
+    self.stack_base = None;
+    res
+  }
+
+  fn stack_left(&self) -> usize {
+    match self.stack_base {
+      Some(base) => self.max_stack.saturating_sub(stack::used_since(base)),
+      None => self.max_stack,
+    }
+  }
 
-    environment.define(
-      "clock".to_string(),
-      Lit::Func(Function::new_native(0, |_, _| {
-        use std::time::{SystemTime, UNIX_EPOCH};
+  // errors without a token, `call` pins it on the innermost call
+  fn check_stack(&self) -> LoxResult<()> {
+    if self.stack_left() > 0 {
+      return Ok(());
+    }
+    match self.call_stack.last() {
+      Some(top) => Err(LoxError::other(format!("Stack overflow: {} calls deep in '{}'.", self.call_stack.len(), top))),
+      None => Err(LoxError::other(format!("Stack overflow: nested too deeply."))),
+    }
+  }
 
-        Ok(Lit::Num(SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards").as_millis() as f64))
-      }))
-    );
+  pub fn set_host_env(&mut self, vars: HashMap<String, String>) {
+    self.host_env = Some(vars);
+  }
 
-    Interpreter {
-      environment,
+  pub fn host_var(&self, name: &str) -> Option<String> {
+    match self.host_env {
+      Some(ref vars) => vars.get(name).cloned(),
+      None => std::env::var(name).ok(),
     }
   }
 
+  pub fn define(&mut self, name: &str, value: Lit) {
+    self.environment.borrow_mut().define(name.to_string(), value);
+  }
+
+  pub fn define_native(&mut self, name: &str, arity: usize, body: NativeFn) {
+    self.define(name, Lit::Func(Function::new_native(name, arity, body)));
+  }
+
   pub fn interpret(&mut self, statements: &Vec<Stmt>) -> LoxResult<()> {
-    for statement in statements {
-      self.execute(statement)?;
+    self.budgeted(|interpreter| {
+      for statement in statements {
+        interpreter.execute(statement)?;
+      }
+      Ok(())
+    })
+  }
+
+  // like interpret, but yields the value of a trailing expression statement
+  pub fn interpret_value(&mut self, statements: &Vec<Stmt>) -> LoxResult<Lit> {
+    self.budgeted(|interpreter| {
+      let mut value = Lit::Nil;
+      for statement in statements {
+        value = match statement {
+          Stmt::Expression { expr } => {
+            // skips `execute`, so count it here
+            if let Some(ref mut profiler) = interpreter.profiler {
+              profiler.count(statement.name());
+            }
+            interpreter.evaluate(expr)?
+          }
+          stmt => {
+            interpreter.execute(stmt)?;
+            Lit::Nil
+          }
+        };
+      }
+      Ok(value)
+    })
+  }
+
+  pub fn eval_str(&mut self, source: String) -> LoxResult<Lit> {
+    let (tokens, errors) = Scanner::new(source).scan_tokens();
+    if !errors.is_empty() {
+      return Err(Interpreter::invalid_source(errors));
     }
-    Ok(())
+
+    let mut parser = Parser::new(tokens);
+    parser.set_trailing_expression(true);
+    // eval runs inside a program, so what it parses has to fit in what's left
+    parser.set_max_stack(self.stack_left());
+    let (statements, errors) = parser.parse();
+    if !errors.is_empty() {
+      return Err(Interpreter::invalid_source(errors));
+    }
+    self.interpret_value(&statements)
+  }
+
+  // folds everything wrong with eval'd source into one error,
+  // `call` then pins it on the `eval(...)` call
+  fn invalid_source(errors: Vec<LoxError>) -> LoxError {
+    let errors = errors.iter().map(|err| err.to_string()).collect::<Vec<_>>();
+    LoxError::other(format!("Invalid source: {}", errors.join(", ")))
   }
 
   fn execute(&mut self, stmt: &Stmt) -> LoxResult<()> {
+    self.check_stack()?;
+    if let Some(ref mut profiler) = self.profiler {
+      profiler.count(stmt.name());
+    }
     stmt.accept(self)
   }
-  pub fn execute_block(&mut self, statements: &Vec<Stmt>, mut environment: Environment) -> LoxResult<()> {
-    mem::swap(&mut self.environment, &mut environment);
-    self.environment.set_enclosing(environment);
+  pub fn execute_block(&mut self, statements: &[Stmt], environment: Environment) -> LoxResult<()> {
+    let previous = mem::replace(&mut self.environment, Rc::new(RefCell::new(environment)));
 
     let mut iter = statements.iter();
 
@@ -118,20 +332,81 @@ impl Interpreter {
       }
     };
 
-    self.environment = self.environment.take_enclosing()?;
+    self.environment = previous;
 
     res
   }
 
+  // calls a global function from the host, e.g. the test runner
+  pub fn call_global(&mut self, name: &Token, args: Vec<Lit>) -> LoxResult<Lit> {
+    let callee = self.environment.borrow().get(name)?;
+    self.budgeted(|interpreter| interpreter.call(callee, args, name))
+  }
+
+  fn call(&mut self, callee: Lit, args: Vec<Lit>, paren: &Token) -> LoxResult<Lit> {
+    match callee {
+      Lit::Func(function) => {
+        if args.len() != function.arity() {
+          return Err(LoxError::runtime(paren.clone(), format!("Expected {} arguments but got {}.", function.arity(), args.len())));
+        }
+        if self.call_stack.len() >= self.max_call_depth || self.stack_left() == 0 {
+          let top = self.call_stack.last().map_or(function.name(), |name| name.as_str());
+          return Err(LoxError::runtime(paren.clone(), format!("Stack overflow: {} calls deep in '{}'.", self.call_stack.len(), top)));
+        }
+
+        let start = self.profiler.as_ref().map(|_| Instant::now());
+        self.call_stack.push(function.name().to_string());
+        let res = function.call(self, args);
+        self.call_stack.pop();
+        if let (Some(profiler), Some(start)) = (self.profiler.as_mut(), start) {
+          profiler.record_call(function.name(), start.elapsed());
+        }
+
+        res.map_err(|err| match err {
+          LoxError::Other { message } => LoxError::runtime(paren.clone(), message),
+          err => err,
+        })
+      }
+      _ => Err(LoxError::runtime(paren.clone(), format!("Can only call functions and classes.")))
+    }
+  }
+
   fn evaluate(&mut self, expr: &Expr) -> LoxResult<Lit> {
+    self.check_stack()?;
+    if let Some(ref mut profiler) = self.profiler {
+      profiler.count(expr.name());
+    }
     expr.accept(self)
   }
 
-  fn is_truthy(&self, lit: &Lit) -> bool {
+  // negative indices count from the end, -1 is the last char
+  fn index(&self, object: Lit, index: Lit, bracket: &Token) -> LoxResult<Lit> {
+    let string = match object {
+      Lit::Str(string) => string,
+      other => return Err(LoxError::runtime(bracket.clone(), format!("Can only index strings, got {}.", other))),
+    };
+    let index = match index {
+      Lit::Num(num) if num.fract() == 0.0 => num,
+      other => return Err(LoxError::runtime(bracket.clone(), format!("Index must be an integer but got {}.", other))),
+    };
+
+    let len = string.chars().count();
+    let position = if index < 0.0 { len as f64 + index } else { index };
+    if position < 0.0 || position >= len as f64 {
+      return Err(LoxError::runtime(bracket.clone(), format!("Index {} is out of range for a string of length {}.", index, len)));
+    }
+
+    Ok(Lit::Str(string.chars().nth(position as usize).ok_or(NoneError)?.to_string()))
+  }
+
+  pub fn is_truthy(&self, lit: &Lit) -> bool {
+    lit.is_truthy()
+  }
+
+  pub fn stringify(&self, lit: &Lit) -> String {
     match lit {
-      Lit::Nil => false,
-      Lit::Bool(b) => *b,
-      _ => true,
+      Lit::Str(st) => st.clone(),
+      lit => lit.to_string(),
     }
   }
 
@@ -150,6 +425,7 @@ impl Interpreter {
   {
     match a {
       Lit::Num(num) => Ok(f(*num)),
+      Lit::Nil if self.lenient_arithmetic => Ok(Lit::Nil),
       _ => Err(LoxError::runtime(op.clone(), format!("Operand must be a number")))
     }
   }
@@ -160,6 +436,7 @@ impl Interpreter {
   {
     match (a, b) {
       (Lit::Num(a), Lit::Num(b)) => Ok(f(*a, *b)),
+      (Lit::Nil, _) | (_, Lit::Nil) if self.lenient_arithmetic => Ok(Lit::Nil),
       _ => Err(LoxError::runtime(op.clone(), format!("Operands must be a numbers")))
     }
   }
@@ -193,6 +470,7 @@ impl ExprVisitor<LoxResult<Lit>> for Interpreter {
           }
           Slash => self.check_number_operands(op, &left, &right, |a, b| Num(a / b)),
           Star => self.check_number_operands(op, &left, &right, |a, b| Num(a * b)),
+          Comma => Ok(right),
           _ => Err(LoxError::runtime(op.clone(), format!("Unreachable")))
         }
       }
@@ -204,18 +482,23 @@ impl ExprVisitor<LoxResult<Lit>> for Interpreter {
           args.push(self.evaluate(arg)?);
         }
 
-        match callee {
-          Func(function) => {
-            if args.len() != function.arity() {
-              return Err(LoxError::runtime(paren.clone(), format!("Expected {} arguments but got {}.", function.arity(), args.len())));
-            }
-            function.call(self, args)
-          }
-          _ => Err(LoxError::runtime(paren.clone(), format!("Can only call functions and classes.")))
-        }
+        self.call(callee, args, paren)
       }
       Grouping { ref expr } => {
-        expr.accept(self)
+        self.evaluate(expr)
+      }
+      Index { ref object, ref bracket, ref index } => {
+        let object = self.evaluate(object)?;
+        let index = self.evaluate(index)?;
+        self.index(object, index, bracket)
+      }
+      Interpolation { ref parts } => {
+        let mut result = String::new();
+        for part in parts {
+          let value = self.evaluate(part)?;
+          result.push_str(&self.stringify(&value));
+        }
+        Ok(Str(result))
       }
       Literal { ref lit } => {
         Ok(lit.clone())
@@ -223,16 +506,22 @@ impl ExprVisitor<LoxResult<Lit>> for Interpreter {
       Logical { ref left, ref op, ref right } => {
         let left = self.evaluate(left)?;
 
-        if op.ty == Or && self.is_truthy(&left) {
-          return Ok(left);
-        } else if op.ty == And && !self.is_truthy(&left) {
+        let truthy = self.is_truthy(&left);
+        if (op.ty == Or && truthy) || (op.ty == And && !truthy) {
           return Ok(left);
         }
 
         self.evaluate(right)
       }
+      Expr::Tuple { ref elements } => {
+        let mut values = Vec::new();
+        for element in elements {
+          values.push(self.evaluate(element)?);
+        }
+        Ok(Lit::Tuple(values))
+      }
       Unary { ref op, ref right } => {
-        let right = self.evaluate(&right)?;
+        let right = self.evaluate(right)?;
         match op.ty {
           Bang => Ok(Lit::Bool(!self.is_truthy(&right))),
           Minus => self.check_number_operand(op, &right, |a| Num(-a)),
@@ -240,11 +529,17 @@ impl ExprVisitor<LoxResult<Lit>> for Interpreter {
         }
       }
       Variable { ref name } => {
-        self.environment.get(name)
+        match self.environment.borrow().get(name) {
+          Err(_) if self.lenient_undefined => Ok(Nil),
+          res => res,
+        }
       }
       Assign { ref name, ref value } => {
         let value = self.evaluate(value)?;
-        self.environment.assign(name, value.clone())?;
+        let old = self.environment.borrow_mut().assign(name, value.clone())?;
+        if let Some(ref mut recorder) = self.recorder {
+          recorder.record(name, Some(old), value.clone());
+        }
         Ok(value)
       }
     }
@@ -255,8 +550,22 @@ impl StmtVisitor<LoxResult<()>> for Interpreter {
   fn visit(&mut self, expr: &Stmt) -> LoxResult<()> {
 
     match expr {
+      Stmt::Assert { .. } if self.strip_asserts => {}
+      Stmt::Assert { ref keyword, ref condition, ref message } => {
+        let condition = self.evaluate(condition)?;
+        if !condition.is_truthy() {
+          let message = match message {
+            Some(message) => {
+              let message = self.evaluate(message)?;
+              format!("Assertion failed: {}", self.stringify(&message))
+            }
+            None => format!("Assertion failed."),
+          };
+          return Err(LoxError::runtime(keyword.clone(), message));
+        }
+      }
       Stmt::Block { ref statements } => {
-        self.execute_block(statements, Environment::new(None))?;
+        self.execute_block(statements, Environment::new(Some(self.environment.clone())))?;
       }
       Stmt::Expression { ref expr } => {
         self.evaluate(expr)?;
@@ -270,7 +579,29 @@ impl StmtVisitor<LoxResult<()>> for Interpreter {
         }
       }
       Stmt::Print { ref expr } => {
-        println!("{}", self.evaluate(expr)?);
+        let value = self.evaluate(expr)?;
+        let line = self.stringify(&value);
+        self.print(&line);
+      }
+      Stmt::Return { ref value, .. } => {
+        let value = match value {
+          Some(value) => self.evaluate(value)?,
+          None => Lit::Nil,
+        };
+        return Err(LoxError::Return { value });
+      }
+      Stmt::Destructure { ref paren, ref names, ref init } => {
+        let values = match self.evaluate(init)? {
+          Lit::Tuple(values) if values.len() == names.len() => values,
+          value => {
+            let message = format!("Can't destructure {} into {} variables.", self.stringify(&value), names.len());
+            return Err(LoxError::runtime(paren.clone(), message));
+          }
+        };
+        let environment = self.environment.clone();
+        for (name, value) in names.iter().zip(values) {
+          self.define_var(&mut environment.borrow_mut(), name, value);
+        }
       }
       Stmt::Var { ref name, ref init } => {
         let value = if let Some(init) = init {
@@ -278,21 +609,349 @@ impl StmtVisitor<LoxResult<()>> for Interpreter {
         } else {
           Lit::Nil
         };
-        self.environment.define(name.raw.clone(), value);
+        let environment = self.environment.clone();
+        self.define_var(&mut environment.borrow_mut(), name, value);
       }
       Stmt::While { ref condition, ref body } => {
         while {
           let condition = self.evaluate(condition)?;
           self.is_truthy(&condition)
         } {
+          if let Some(ref mut profiler) = self.profiler {
+            profiler.count("Stmt::While body");
+          }
           self.execute(body)?;
         }
       },
-      Stmt::Function { ref name, ref params, ref body } => {
-        self.environment.define(name.raw.clone(), Lit::Func(Function::new(name.raw.clone(), params.clone(), body.clone())))
+      Stmt::Function { ref name, ref params, ref body, .. } => {
+        // the closure is the environment the name is defined in, so the body can call itself
+        let function = Function::new(name.raw.clone(), params.clone(), body.clone(), self.environment.clone());
+        let environment = self.environment.clone();
+        self.define_var(&mut environment.borrow_mut(), name, Lit::Func(function));
       }
     }
 
     Ok(())
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse(source: &str) -> LoxResult<Vec<Stmt>> {
+    let (tokens, mut errors) = Scanner::new(source.to_string()).scan_tokens();
+    if !errors.is_empty() {
+      return Err(errors.remove(0));
+    }
+    let (statements, mut errors) = Parser::new(tokens).parse();
+    if !errors.is_empty() {
+      return Err(errors.remove(0));
+    }
+    Ok(statements)
+  }
+
+  fn run_with(interpreter: &mut Interpreter, source: &str) -> LoxResult<String> {
+    interpreter.capture_output();
+    let statements = parse(source)?;
+    interpreter.interpret(&statements)?;
+    Ok(interpreter.take_output())
+  }
+
+  fn run(source: &str) -> String {
+    run_with(&mut Interpreter::new(), source).unwrap_or_else(|err| panic!("{}", err))
+  }
+
+  fn fail(source: &str) -> LoxError {
+    run_with(&mut Interpreter::new(), source).expect_err("expected an error")
+  }
+
+  #[test]
+  fn return_leaves_the_function_early() {
+    let output = run("fun f() { print 1; return 2; print 3; } print f();");
+    assert_eq!(output, "1\n2\n");
+  }
+
+  #[test]
+  fn bare_return_yields_nil() {
+    assert_eq!(run("fun f() { return; } print f();"), "nil\n");
+  }
+
+  #[test]
+  fn return_inside_while_stops_the_loop() {
+    let output = run("
+      fun first_over(limit) {
+        var i = 0;
+        while (true) {
+          i = i + 1;
+          if (i > limit) return i;
+          print i;
+        }
+      }
+      print first_over(2);
+    ");
+    assert_eq!(output, "1\n2\n3\n");
+  }
+
+  #[test]
+  fn eval_yields_the_trailing_expression() {
+    assert_eq!(run("print eval(\"1 + 2\");"), "3\n");
+  }
+
+  #[test]
+  fn eval_raises_syntax_errors() {
+    let err = fail("eval(\"1 +\");");
+    assert!(matches!(err, LoxError::RuntimeError { .. }), "{}", err);
+  }
+
+  #[test]
+  fn eval_reports_every_lex_error() {
+    let err = fail("eval(\"# ~\");");
+    let message = err.to_string();
+    assert!(message.contains("'#'") && message.contains("'~'"), "{}", message);
+  }
+
+  #[test]
+  fn call_depth_is_configurable() {
+    let source = "fun down(n) { if (n > 0) down(n - 1); }";
+    let mut interpreter = Interpreter::new();
+    interpreter.set_max_call_depth(10);
+    let err = run_with(&mut interpreter, &format!("{} down(50);", source)).expect_err("expected a stack overflow");
+    assert_eq!(err.to_string(), "[Line: 1:36] RuntimeError: Stack overflow: 10 calls deep in 'down'.");
+    assert!(run_with(&mut interpreter, &format!("{} down(8);", source)).is_ok());
+
+    interpreter.set_max_call_depth(5);
+    assert!(run_with(&mut interpreter, &format!("{} down(8);", source)).is_err());
+  }
+
+  #[test]
+  fn default_limits_hold_on_a_test_thread() {
+    // these would overflow the test thread's stack and abort the whole run without a stack budget
+    let err = fail("fun f(n) { if (n > 0) f(n - 1); } f(100000);");
+    assert!(err.to_string().contains("RuntimeError: Stack overflow:"), "{}", err);
+
+    let err = fail("fun f(n) { { { return 1 + (1 + (1 + (1 + f(n - 1)))); } } } f(100000);");
+    assert!(err.to_string().contains("RuntimeError: Stack overflow:"), "{}", err);
+
+    let err = fail(&format!("print {}1;", "-".repeat(150)));
+    assert!(err.to_string().contains("Stack overflow: nested too deeply."), "{}", err);
+
+    assert_eq!(run("fun f(n) { if (n > 0) return f(n - 1) + 1; return 0; } print f(10);"), "10\n");
+  }
+
+  #[test]
+  fn undefined_variables_are_nil_when_lenient() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_lenient_undefined(true);
+    assert_eq!(run_with(&mut interpreter, "print missing;").ok().as_deref(), Some("nil\n"));
+
+    let err = fail("print missing;");
+    assert!(err.to_string().contains("Undefined variable"), "{}", err);
+  }
+
+  #[test]
+  fn nil_arithmetic_is_nil_when_lenient() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_lenient_arithmetic(true);
+    let output = run_with(&mut interpreter, "print nil + 1; print nil + \"s\"; print nil < 1; print -nil;");
+    assert_eq!(output.ok().as_deref(), Some("nil\nnil\nnil\nnil\n"));
+
+    let err = fail("print nil + 1;");
+    assert!(matches!(err, LoxError::RuntimeError { .. }), "{}", err);
+  }
+
+  #[test]
+  fn recorder_sees_every_write() {
+    let mut interpreter = Interpreter::new();
+    interpreter.enable_recorder();
+    assert!(run_with(&mut interpreter, "var x = 1;\nx = 2;\nx = \"three\";").is_ok());
+
+    let events = interpreter.recorder().expect("recorder is enabled").events();
+    let writes = events.iter()
+      .map(|event| (event.name.as_str(), event.old.clone(), event.new.clone(), event.pos.line))
+      .collect::<Vec<_>>();
+    assert!(writes == vec![
+      ("x", None, Lit::Num(1.0), 1),
+      ("x", Some(Lit::Num(1.0)), Lit::Num(2.0), 2),
+      ("x", Some(Lit::Num(2.0)), Lit::Str("three".to_string()), 3),
+    ]);
+  }
+
+  #[test]
+  fn with_globals_defines_host_values() {
+    let mut globals = HashMap::new();
+    globals.insert("name".to_string(), Lit::Str("world".to_string()));
+    let mut interpreter = Interpreter::with_globals(globals);
+    let output = run_with(&mut interpreter, "print \"hello \" + name;");
+    assert_eq!(output.ok().as_deref(), Some("hello world\n"));
+  }
+
+  #[test]
+  fn env_reads_the_host_environment() {
+    let mut vars = HashMap::new();
+    vars.insert("LOX_HOME".to_string(), "/opt/lox".to_string());
+    let mut interpreter = Interpreter::new();
+    interpreter.set_host_env(vars);
+    let output = run_with(&mut interpreter, "print env(\"LOX_HOME\"); print env(\"LOX_MISSING\");");
+    assert_eq!(output.ok().as_deref(), Some("/opt/lox\nnil\n"));
+  }
+
+  #[test]
+  fn failing_assert_reports_its_message() {
+    assert_eq!(run("assert 1 < 2, \"never\"; print 1;"), "1\n");
+
+    let err = fail("var x = 1;\nassert x > 1, \"x is too small\";");
+    assert!(err.to_string() == "[Line: 2:1] RuntimeError: Assertion failed: x is too small", "{}", err);
+  }
+
+  #[test]
+  fn assert_condition_can_be_a_comma_expression() {
+    assert_eq!(run("var x; assert ((x = 1, x > 0)); print x;"), "1\n");
+  }
+
+  #[test]
+  fn stripped_asserts_are_not_evaluated() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_strip_asserts(true);
+    let output = run_with(&mut interpreter, "var x = 1; assert false, x = 2; print x;");
+    assert_eq!(output.ok().as_deref(), Some("1\n"));
+  }
+
+  #[test]
+  fn tuples_destructure_into_variables() {
+    let output = run("
+      fun minmax(a, b) { if (a < b) return a, b; return b, a; }
+      var (lo, hi) = minmax(5, 2);
+      print lo;
+      print hi;
+      print minmax(1, 2);
+    ");
+    assert_eq!(output, "2\n5\n(1, 2)\n");
+  }
+
+  #[test]
+  fn destructuring_needs_a_matching_tuple() {
+    let err = fail("fun pair() { return 1, 2; } var (a, b, c) = pair();");
+    assert_eq!(err.to_string(), "[Line: 1:33] RuntimeError: Can't destructure (1, 2) into 3 variables.");
+
+    let err = fail("var (a, b) = 1;");
+    assert_eq!(err.to_string(), "[Line: 1:5] RuntimeError: Can't destructure 1 into 2 variables.");
+  }
+
+  #[test]
+  fn interpolation() {
+    assert_eq!(run("var n = 3; print \"n=${n + 1}\";"), "n=4\n");
+    assert_eq!(run("print \"a${1}b${2}c\";"), "a1b2c\n");
+  }
+
+  #[test]
+  fn escaped_interpolation_is_literal() {
+    assert_eq!(run("print \"\\${n}\";"), "${n}\n");
+  }
+
+  #[test]
+  fn interpolation_with_strings_inside() {
+    assert_eq!(run("var name = \"lox\"; print \"hi ${\"dear \" + name}\";"), "hi dear lox\n");
+    assert_eq!(run("print \"${\"in ${1 + 1}\"}\";"), "in 2\n");
+  }
+
+  #[test]
+  fn print_takes_grouped_expressions() {
+    assert_eq!(run("print (1 + 2) * 3;"), "9\n");
+    assert_eq!(run("fun f() { return \"abc\"; } print (f())[1];"), "b\n");
+    assert_eq!(run("fun adder(a) { fun add(b) { return a + b; } return add; } print (adder(1)(2) + 3) * 2;"), "12\n");
+  }
+
+  #[test]
+  fn calls_see_a_reassigned_function() {
+    let output = run("
+      fun first() { return 1; }
+      fun second() { return 2; }
+      var f = first;
+      fun call() { return f(); }
+      print call();
+      f = second;
+      print call();
+      first = second;
+      print first();
+    ");
+    assert_eq!(output, "1\n2\n2\n");
+  }
+
+  #[test]
+  fn recursive_fib() {
+    let output = run("fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); } print fib(10);");
+    assert_eq!(output, "55\n");
+  }
+
+  #[test]
+  fn functions_can_call_themselves_from_nested_scopes() {
+    let output = run("
+      {
+        fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }
+        print fib(10) == 55;
+        print fib(10) != 55;
+      }
+    ");
+    assert_eq!(output, "true\nfalse\n");
+  }
+
+  #[test]
+  fn panicking_native_is_a_runtime_error() {
+    fn explode(_: &mut Interpreter, _: Vec<Lit>) -> LoxResult<Lit> {
+      panic!("boom");
+    }
+
+    let mut interpreter = Interpreter::new();
+    interpreter.define_native("explode", 0, explode);
+    let err = run_with(&mut interpreter, "explode();").expect_err("expected the panic to be caught");
+    assert!(matches!(err, LoxError::RuntimeError { .. }), "{}", err);
+    assert!(err.to_string().contains("Native function panicked: boom"), "{}", err);
+  }
+
+  #[test]
+  fn profiler_counts_loop_iterations() {
+    let mut interpreter = Interpreter::new();
+    interpreter.enable_profiler();
+    assert!(run_with(&mut interpreter, "var i = 0; while (i < 7) i = i + 1;").is_ok());
+
+    let profiler = interpreter.profiler().expect("profiler is enabled");
+    assert_eq!(profiler.count_of("Stmt::While"), 1);
+    assert_eq!(profiler.count_of("Stmt::While body"), 7);
+  }
+
+  #[test]
+  fn for_loops() {
+    assert_eq!(run("for (var i = 0; i < 3; i = i + 1) print i;"), "0\n1\n2\n");
+    assert_eq!(run("var i = 5; for (; i > 3;) i = i - 1; print i;"), "3\n");
+    assert_eq!(run("fun f() { for (;;) return 1; } print f();"), "1\n");
+  }
+
+  #[test]
+  fn for_increment_can_be_a_comma_expression() {
+    let output = run("var i; var j; for (i = 0, j = 3; i < j; i = i + 1, j = j - 1) print i + j * 10;");
+    assert_eq!(output, "30\n21\n");
+  }
+
+  #[test]
+  fn string_indexing() {
+    assert_eq!(run("var s = \"héllo\"; print s[0]; print s[1]; print s[4];"), "h\né\no\n");
+    assert_eq!(run("print \"abc\"[-1]; print \"abc\"[-3];"), "c\na\n");
+  }
+
+  #[test]
+  fn bad_string_indices() {
+    let message = |source: &str| fail(source).to_string();
+    assert_eq!(message("\"abc\"[3];"), "[Line: 1:6] RuntimeError: Index 3 is out of range for a string of length 3.");
+    assert_eq!(message("\"abc\"[-4];"), "[Line: 1:6] RuntimeError: Index -4 is out of range for a string of length 3.");
+    assert_eq!(message("\"abc\"[0.5];"), "[Line: 1:6] RuntimeError: Index must be an integer but got 0.5.");
+    assert_eq!(message("\"abc\"[\"0\"];"), "[Line: 1:6] RuntimeError: Index must be an integer but got \"0\".");
+    assert_eq!(message("123[0];"), "[Line: 1:4] RuntimeError: Can only index strings, got 123.");
+  }
+
+  #[test]
+  fn return_before_closing_brace_needs_no_semicolon() {
+    assert_eq!(run("fun f() { print 1; return } print f();"), "1\nnil\n");
+    assert_eq!(run("fun f(x) { if (x) { return } print 2; } f(true); f(false);"), "2\n");
+  }
+}
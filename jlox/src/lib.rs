@@ -0,0 +1,15 @@
+// errors are built with `format!` throughout, even without arguments, and carry whole tokens
+#![allow(clippy::useless_format, clippy::result_large_err, clippy::upper_case_acronyms)]
+
+pub mod lox;
+pub mod scanner;
+pub mod pos;
+pub mod expr;
+pub mod parser;
+pub mod err;
+pub mod interpreter;
+pub mod lit;
+pub mod prelude;
+pub mod profiler;
+pub mod recorder;
+pub mod stack;
@@ -1,13 +1,18 @@
 use std::fmt::Formatter;
 use std::fmt::Error;
+use std::cmp::Ordering;
 use crate::{
   expr::Stmt,
   interpreter::Interpreter,
+  err::LoxError,
   err::LoxResult,
+  err::NoneError,
   scanner::Token
 };
 use crate::interpreter::Environment;
 use std::cell::RefCell;
+use std::rc::Rc;
+use std::panic::{self, AssertUnwindSafe};
 
 pub trait Callable {
   fn arity(&self) -> usize;
@@ -15,9 +20,9 @@ pub trait Callable {
   fn to_string(&self) -> String;
 }
 
-thread_local!(static NATIVE_FN_ID: RefCell<usize> = RefCell::new(0));
+thread_local!(static NATIVE_FN_ID: RefCell<usize> = const { RefCell::new(0) });
 
-pub type NativeFn = fn(&Interpreter, Vec<Lit>) -> LoxResult<Lit>;
+pub type NativeFn = fn(&mut Interpreter, Vec<Lit>) -> LoxResult<Lit>;
 
 #[derive(Clone)]
 struct NativeFuntion {
@@ -36,8 +41,15 @@ impl NativeFuntion {
     }
   }
 
+  // a panicking native becomes an error instead of taking the whole process down
   pub fn call(&self, interpreter: &mut Interpreter, args: Vec<Lit>) -> LoxResult<Lit> {
-    (self.body)(interpreter, args)
+    let body = self.body;
+    panic::catch_unwind(AssertUnwindSafe(|| body(interpreter, args))).unwrap_or_else(|payload| {
+      let reason = payload.downcast_ref::<&str>().map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| format!("unknown cause"));
+      Err(LoxError::other(format!("Native function panicked: {}", reason)))
+    })
   }
 }
 
@@ -47,15 +59,29 @@ impl PartialEq for NativeFuntion {
   }
 }
 
-#[derive(PartialEq, Clone)]
+#[derive(Clone)]
 enum InternalFunc {
   Native(NativeFuntion),
   User {
-    params: Vec<Token>,
-    body: Vec<Stmt>,
+    params: Rc<Vec<Token>>,
+    body: Rc<Vec<Stmt>>,
+    closure: Rc<RefCell<Environment>>,
   },
 }
 
+impl PartialEq for InternalFunc {
+  fn eq(&self, other: &InternalFunc) -> bool {
+    match (self, other) {
+      (InternalFunc::Native(a), InternalFunc::Native(b)) => a == b,
+      (
+        InternalFunc::User { params: a_params, body: a_body, closure: a_closure },
+        InternalFunc::User { params: b_params, body: b_body, closure: b_closure },
+      ) => Rc::ptr_eq(a_params, b_params) && Rc::ptr_eq(a_body, b_body) && Rc::ptr_eq(a_closure, b_closure),
+      _ => false,
+    }
+  }
+}
+
 #[derive( PartialEq, Clone)]
 pub struct Function {
   arity: usize,
@@ -66,27 +92,33 @@ pub struct Function {
 impl Function {
   pub fn new(
     name: String,
-    params: Vec<Token>,
-    body: Vec<Stmt>,
+    params: Rc<Vec<Token>>,
+    body: Rc<Vec<Stmt>>,
+    closure: Rc<RefCell<Environment>>,
   ) -> Self {
     Function {
       arity: params.len(),
       body: InternalFunc::User {
         params,
         body,
+        closure,
       },
       name,
     }
   }
 
-  pub fn new_native(arity: usize, body: NativeFn) -> Self {
+  pub fn new_native(name: &str, arity: usize, body: NativeFn) -> Self {
     Function {
       arity,
       body: InternalFunc::Native(NativeFuntion::new(body)),
-      name: "native".to_string(),
+      name: name.to_string(),
     }
   }
 
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
   pub fn arity(&self) -> usize {
     self.arity
   }
@@ -94,21 +126,27 @@ impl Function {
   pub fn call(&self, interpreter: &mut Interpreter, args: Vec<Lit>) -> LoxResult<Lit> {
     match self.body {
       InternalFunc::Native(ref func) => func.call(interpreter, args),
-      InternalFunc::User { ref body, ref params } => {
-        let mut environment = Environment::new(None);
+      InternalFunc::User { ref body, ref params, ref closure } => {
+        let mut environment = Environment::new(Some(closure.clone()));
 
         for (i, arg) in args.into_iter().enumerate() {
-          environment.define(params.get(i)?.raw.clone(), arg)
+          interpreter.define_var(&mut environment, params.get(i).ok_or(NoneError)?, arg)
         }
 
-        interpreter.execute_block(body, environment);
-        Ok(Lit::Nil)
+        match interpreter.execute_block(body, environment) {
+          Ok(()) => Ok(Lit::Nil),
+          Err(LoxError::Return { value }) => Ok(value),
+          Err(err) => Err(err),
+        }
       }
     }
   }
 
-  pub fn to_string(&self) -> String {
-    format!("<fn {}>", self.name)
+}
+
+impl ::std::fmt::Display for Function {
+  fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+    write!(f, "<fn {}>", self.name)
   }
 }
 
@@ -118,17 +156,95 @@ pub enum Lit {
   Num(f64),
   Bool(bool),
   Func(Function),
+  // several values returned at once, compared element by element
+  Tuple(Vec<Lit>),
   Nil,
 }
 
+impl Lit {
+  /// Whether the value counts as true in a condition, only `nil` and `false` don't.
+  ///
+  /// Natives should use this instead of matching on the value themselves:
+  ///
+  /// ```
+  /// use jlox::{err::LoxResult, interpreter::Interpreter, lit::Lit};
+  ///
+  /// fn not(_: &mut Interpreter, args: Vec<Lit>) -> LoxResult<Lit> {
+  ///   Ok(Lit::Bool(!args[0].is_truthy()))
+  /// }
+  ///
+  /// let mut interpreter = Interpreter::new();
+  /// interpreter.define_native("not", 1, not);
+  /// assert!(interpreter.eval_str("not(0)".to_string()).ok() == Some(Lit::Bool(false)));
+  /// assert!(interpreter.eval_str("not(nil)".to_string()).ok() == Some(Lit::Bool(true)));
+  /// ```
+  pub fn is_truthy(&self) -> bool {
+    match self {
+      Lit::Nil => false,
+      Lit::Bool(b) => *b,
+      _ => true,
+    }
+  }
+}
+
+// numbers, strings and bools order among themselves (false < true),
+// anything else is only comparable to itself
+impl PartialOrd for Lit {
+  fn partial_cmp(&self, other: &Lit) -> Option<Ordering> {
+    match (self, other) {
+      (Lit::Num(a), Lit::Num(b)) => a.partial_cmp(b),
+      (Lit::Str(a), Lit::Str(b)) => a.partial_cmp(b),
+      (Lit::Bool(a), Lit::Bool(b)) => a.partial_cmp(b),
+      (a, b) if a == b => Some(Ordering::Equal),
+      _ => None,
+    }
+  }
+}
+
 impl ::std::fmt::Display for Lit {
   fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
     match self {
-      Lit::Nil => write!(f, "{}", "nil"),
+      Lit::Nil => write!(f, "nil"),
       Lit::Num(num) => write!(f, "{}", num),
       Lit::Bool(b) => write!(f, "{}", b),
       Lit::Str(st) => write!(f, "{:?}", st),
-      Lit::Func(func) => write!(f, "{}", func.to_string()),
+      Lit::Func(func) => write!(f, "{}", func),
+      Lit::Tuple(values) => {
+        let values = values.iter().map(|value| value.to_string()).collect::<Vec<_>>();
+        write!(f, "({})", values.join(", "))
+      }
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn s(value: &str) -> Lit {
+    Lit::Str(value.to_string())
+  }
+
+  #[test]
+  fn same_types_order_among_themselves() {
+    assert_eq!(Lit::Num(1.0).partial_cmp(&Lit::Num(2.0)), Some(Ordering::Less));
+    assert_eq!(s("b").partial_cmp(&s("a")), Some(Ordering::Greater));
+    assert_eq!(Lit::Bool(false).partial_cmp(&Lit::Bool(true)), Some(Ordering::Less));
+    assert_eq!(Lit::Nil.partial_cmp(&Lit::Nil), Some(Ordering::Equal));
+    assert_eq!(Lit::Num(f64::NAN).partial_cmp(&Lit::Num(1.0)), None);
+  }
+
+  #[test]
+  fn mixed_types_dont_order() {
+    assert_eq!(Lit::Num(1.0).partial_cmp(&s("1")), None);
+    assert_eq!(Lit::Bool(true).partial_cmp(&Lit::Num(1.0)), None);
+    assert_eq!(Lit::Nil.partial_cmp(&Lit::Bool(false)), None);
+  }
+
+  #[test]
+  fn tuples_only_compare_equal() {
+    let pair = |a, b| Lit::Tuple(vec![Lit::Num(a), Lit::Num(b)]);
+    assert_eq!(pair(1.0, 2.0).partial_cmp(&pair(1.0, 2.0)), Some(Ordering::Equal));
+    assert_eq!(pair(1.0, 2.0).partial_cmp(&pair(1.0, 3.0)), None);
+  }
+}
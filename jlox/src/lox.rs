@@ -18,31 +18,67 @@ use std::{
 use crate::{
   err::LoxError,
   err::LoxResult,
+  expr::Stmt,
   interpreter::Interpreter,
+  lit::Lit,
   parser::Parser,
   scanner::{
     Scanner,
-    TokenType,
   },
 };
 
 pub struct Lox {
   interpreter: Interpreter,
+  relaxed_semicolons: bool,
+  // stack the parser may use, its own default when unset
+  max_parse_stack: Option<usize>,
+  // exit run_file with the script's final numeric value as the status
+  exit_from_result: bool,
+}
+
+impl Default for Lox {
+  fn default() -> Self {
+    Lox::new()
+  }
 }
 
 impl Lox {
   pub fn new() -> Self {
     Lox {
       interpreter: Interpreter::new(),
+      relaxed_semicolons: false,
+      max_parse_stack: None,
+      exit_from_result: false,
     }
   }
 
+  pub fn set_relaxed_semicolons(&mut self, relaxed: bool) {
+    self.relaxed_semicolons = relaxed;
+  }
+
+  pub fn set_max_parse_stack(&mut self, bytes: usize) {
+    self.max_parse_stack = Some(bytes);
+  }
+
+  pub fn set_exit_from_result(&mut self, exit: bool) {
+    self.exit_from_result = exit;
+  }
+
+  pub fn interpreter(&mut self) -> &mut Interpreter {
+    &mut self.interpreter
+  }
+
   pub fn run_file(&mut self, filename: String) -> Result<(), Error> {
 //    let dir = env::current_dir()?;
 //    Path::
     let path = Path::new(&filename);
-    let content = fs::read_to_string(&path)?;
-    match self.run(content) {
+    let content = fs::read_to_string(path)?;
+    let result = self.parse(content).and_then(|statements| self.interpreter.interpret_value(&statements));
+    match result {
+      Ok(Lit::Num(num)) if self.exit_from_result => {
+        // NaN stays NaN through clamp, `as` turns it into 0
+        std::process::exit(num.clamp(0.0, 255.0) as i32);
+      }
       Ok(_) => {}
       Err(err) => {
         Lox::report(err);
@@ -53,13 +89,90 @@ impl Lox {
     Ok(())
   }
 
+  // runs the file's top level, then calls its `fun main()`
+  pub fn run_main(&mut self, filename: String) -> Result<(), Error> {
+    let statements = self.run_top_level(&filename)?;
+
+    let main = statements.iter().find_map(|stmt| match stmt {
+      Stmt::Function { name, params, .. } if name.raw == "main" && params.is_empty() => Some(name),
+      _ => None,
+    });
+
+    match main {
+      Some(name) => {
+        if let Err(err) = self.interpreter.call_global(name, Vec::new()) {
+          Lox::report(err);
+          std::process::exit(1);
+        }
+      }
+      None => {
+        println!("No 'fun main()' defined in {}.", filename);
+        std::process::exit(1);
+      }
+    }
+
+    Ok(())
+  }
+
+  // runs the file, then every zero-argument function marked `@test`,
+  // a test fails if calling it raises an error
+  pub fn run_tests(&mut self, filename: String) -> Result<(), Error> {
+    let statements = self.run_top_level(&filename)?;
+
+    let tests = statements.iter().filter_map(|stmt| match stmt {
+      Stmt::Function { name, params, attributes, .. }
+        if params.is_empty() && attributes.iter().any(|attr| attr.raw == "test") => Some(name),
+      _ => None,
+    });
+
+    let mut passed = 0;
+    let mut failed = 0;
+    for name in tests {
+      match self.interpreter.call_global(name, Vec::new()) {
+        Ok(_) => {
+          println!("test {} ... ok", name.raw);
+          passed += 1;
+        }
+        Err(err) => {
+          println!("test {} ... FAILED", name.raw);
+          Lox::report(err);
+          failed += 1;
+        }
+      }
+    }
+
+    println!();
+    println!("test result: {}. {} passed; {} failed", if failed == 0 { "ok" } else { "FAILED" }, passed, failed);
+    if failed > 0 {
+      std::process::exit(1);
+    }
+
+    Ok(())
+  }
+
+  // runs the file and hands back its statements, exits if that fails
+  fn run_top_level(&mut self, filename: &str) -> Result<Vec<Stmt>, Error> {
+    let content = fs::read_to_string(Path::new(filename))?;
+    let result = self.parse(content).and_then(|statements| {
+      self.interpreter.interpret(&statements)?;
+      Ok(statements)
+    });
+
+    match result {
+      Ok(statements) => Ok(statements),
+      Err(err) => {
+        Lox::report(err);
+        std::process::exit(1);
+      }
+    }
+  }
+
   pub fn run_prompt(&mut self) -> Result<(), Error> {
     let mut stdout = stdout();
     print!("> ");
     stdout.flush()?;
     let input_reader = BufReader::new(stdin());
     for line in input_reader.lines() {
-      ;
       match self.run(line?) {
         Ok(_) => {}
         Err(err) => {
@@ -73,34 +186,61 @@ impl Lox {
   }
 
   pub fn run(&mut self, source: String) -> LoxResult<()> {
-    let scanner = Scanner::new(source);
-    let tokens = scanner.scan_tokens()?;
-    let parser = Parser::new(tokens);
-    let statements = parser.parse()?;
+    let statements = self.parse(source)?;
     self.interpreter.interpret(&statements)?;
     Ok(())
   }
 
-  pub fn report(err: LoxError) {
-    match err {
-      LoxError::ParseError { token, message } => {
-        let cause = if token.ty == TokenType::EOF {
-          " at end".to_string()
-        } else {
-          format!(" at '{}'", token.raw)
-        };
-
-        println!("[Line: {}] Error{}: {}", token.pos, cause, message);
-      }
-      LoxError::LexError { pos, message } => {
-        println!("[Line: {}] Error: {}", pos, message);
-      }
-      LoxError::Other { message } => {
-        println!("[??] Unexpected Error: {}", message);
-      }
-      LoxError::RuntimeError { token, message } => {
-        println!("[Line: {}] RuntimeError: {}", token.pos, message);
-      }
+  fn parse(&mut self, source: String) -> LoxResult<Vec<Stmt>> {
+    let scanner = Scanner::new(source);
+    let (tokens, errors) = scanner.scan_tokens();
+    if !errors.is_empty() {
+      return Err(Lox::report_all_but_last(errors));
     }
+
+    let mut parser = Parser::new(tokens);
+    parser.set_relaxed_semicolons(self.relaxed_semicolons);
+    if let Some(bytes) = self.max_parse_stack {
+      parser.set_max_stack(bytes);
+    }
+    let (statements, errors) = parser.parse();
+    if !errors.is_empty() {
+      return Err(Lox::report_all_but_last(errors));
+    }
+
+    Ok(statements)
+  }
+
+  fn report_all_but_last(mut errors: Vec<LoxError>) -> LoxError {
+    let last = errors.pop().expect("at least one error");
+    for err in errors {
+      Lox::report(err);
+    }
+    last
+  }
+
+  pub fn report(err: LoxError) {
+    println!("{}", err);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn relaxed_semicolons_allow_a_missing_last_semicolon_in_a_block() {
+    let mut lox = Lox::new();
+    assert!(lox.parse("{ print 1 }".to_string()).is_err());
+
+    lox.set_relaxed_semicolons(true);
+    assert!(lox.parse("{ print 1 }".to_string()).is_ok());
+    assert!(lox.parse("{ print 1 print 2; }".to_string()).is_err());
+  }
+
+  #[test]
+  fn invalid_assignment_target_is_an_error() {
+    let err = Lox::new().parse("1 = 2;".to_string()).err().map(|err| err.to_string());
+    assert_eq!(err.as_deref(), Some("[Line: 1:3] Error at '=': Invalid assignment target."));
   }
 }
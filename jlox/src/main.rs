@@ -1,37 +1,114 @@
-#![feature(try_trait, bind_by_move_pattern_guards, duration_as_u128)]
+use std::{
+  env,
+  thread,
+};
 
-mod lox;
-mod scanner;
-mod pos;
-mod expr;
-mod parser;
-mod err;
-mod interpreter;
-mod lit;
+use jlox::lox::Lox;
 
-use std::env;
+// the interpreter recurses on the native stack, so give it plenty to work with
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+// parsing and running a program each get half of that, the rest covers what runs past
+// the budgets before they're checked. A debug build gets a couple of thousand calls deep
+const MAX_STACK: usize = STACK_SIZE / 2;
+// the stack budget is what limits calls here, not a count
+const MAX_CALL_DEPTH: usize = 100_000;
 
-use crate::lox::Lox;
+const USAGE: &str = "Usage: rlox [--profile] [--release] [--exit-from-result | --test | --main] [script]";
 
 fn main() {
-  match env::args().collect::<Vec<_>>().as_slice() {
-    [_] => {
-      // repl
-      if let Err(err) = Lox::new().run_prompt() {
-        println!("{}", err);
-        ::std::process::exit(1);
+  let interpreter = thread::Builder::new()
+    .stack_size(STACK_SIZE)
+    .spawn(run)
+    .expect("Couldn't spawn the interpreter thread");
+
+  if interpreter.join().is_err() {
+    ::std::process::exit(1);
+  }
+}
+
+enum Mode {
+  File,
+  // call the script's `fun main()` after running it
+  Main,
+  // call every `@test` function after running it
+  Test,
+}
+
+struct Options {
+  mode: Mode,
+  profile: bool,
+  release: bool,
+  exit_from_result: bool,
+  // no script starts the repl
+  script: Option<String>,
+}
+
+impl Options {
+  fn parse(args: impl Iterator<Item = String>) -> Option<Options> {
+    let mut options = Options {
+      mode: Mode::File,
+      profile: false,
+      release: false,
+      exit_from_result: false,
+      script: None,
+    };
+
+    for arg in args {
+      match arg.as_str() {
+        _ if options.script.is_some() => return None,
+        "--profile" => options.profile = true,
+        "--release" => options.release = true,
+        "--exit-from-result" => options.exit_from_result = true,
+        "--test" => options.mode = Mode::Test,
+        "--main" => options.mode = Mode::Main,
+        flag if flag.starts_with("--") => return None,
+        _ => options.script = Some(arg),
       }
     }
-    [_, filename] => {
-      // file
-      if let Err(err) = Lox::new().run_file(filename.clone()) {
-        println!("{}", err);
-        ::std::process::exit(1);
-      }
+
+    let is_file = matches!(options.mode, Mode::File);
+    if options.script.is_none() && (!is_file || options.exit_from_result) {
+      return None;
     }
-    _ => {
-      println!("Usage: rlox [script]");
-      std::process::exit(1);
+    if options.exit_from_result && !is_file {
+      return None;
     }
+
+    Some(options)
+  }
+}
+
+fn run() {
+  let options = match Options::parse(env::args().skip(1)) {
+    Some(options) => options,
+    None => {
+      println!("{}", USAGE);
+      ::std::process::exit(1);
+    }
+  };
+
+  let mut lox = Lox::new();
+  lox.set_exit_from_result(options.exit_from_result);
+  lox.set_max_parse_stack(MAX_STACK);
+  lox.interpreter().set_max_call_depth(MAX_CALL_DEPTH);
+  lox.interpreter().set_max_stack(MAX_STACK);
+  lox.interpreter().set_strip_asserts(options.release);
+  if options.profile {
+    lox.interpreter().enable_profiler();
+  }
+
+  let result = match (options.script, options.mode) {
+    (None, _) => lox.run_prompt(),
+    (Some(script), Mode::File) => lox.run_file(script),
+    (Some(script), Mode::Main) => lox.run_main(script),
+    (Some(script), Mode::Test) => lox.run_tests(script),
+  };
+  if let Err(err) = result {
+    println!("{}", err);
+    ::std::process::exit(1);
+  }
+
+  if let Some(profiler) = lox.interpreter().profiler() {
+    eprint!("{}", profiler);
   }
 }
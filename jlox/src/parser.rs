@@ -1,10 +1,11 @@
 use crate::{
   err::LoxError,
   err::LoxResult,
+  err::NoneError,
   expr::Expr,
   expr::Stmt,
-  lox::Lox,
   scanner::{Token, TokenType::{self, *}},
+  stack,
 };
 use crate::lit::Lit;
 
@@ -22,9 +23,30 @@ primary        → NUMBER | STRING | "false" | "true" | "nil"
 
 */
 
+const MAX_NESTING: usize = 255;
+// how much native stack parsing may take, a debug build fits a couple of hundred levels of
+// most constructs into this and the whole parse still fits a 2MB thread
+const MAX_STACK: usize = 512 * 1024;
+
 pub struct Parser {
   tokens: Vec<Token>,
   current: usize,
+  // how many statements / expressions we're currently inside of,
+  // the parser recurses for each so this is capped to not overflow the stack
+  nesting: usize,
+  max_nesting: usize,
+  // what the stack looked like when parsing started and how far past that we may go,
+  // some levels are much heavier than others so the count alone isn't enough
+  stack_base: usize,
+  max_stack: usize,
+  // how many function bodies we're in, `return` is only allowed inside one
+  functions: usize,
+  // allow leaving out the `;` of the last statement in a block
+  relaxed_semicolons: bool,
+  // allow leaving out the `;` of the last statement in the source, for snippets like `1 + 2`
+  trailing_expression: bool,
+  // everything we recovered from, handed back once parsing is done
+  errors: Vec<LoxError>,
 }
 
 impl Parser {
@@ -32,66 +54,159 @@ impl Parser {
     Parser {
       tokens,
       current: 0,
+      nesting: 0,
+      max_nesting: MAX_NESTING,
+      stack_base: 0,
+      max_stack: MAX_STACK,
+      functions: 0,
+      relaxed_semicolons: false,
+      trailing_expression: false,
+      errors: Vec::new(),
     }
   }
 
-  pub fn parse(mut self) -> LoxResult<Vec<Stmt>> {
+  pub fn set_relaxed_semicolons(&mut self, relaxed: bool) {
+    self.relaxed_semicolons = relaxed;
+  }
+
+  pub fn set_trailing_expression(&mut self, allowed: bool) {
+    self.trailing_expression = allowed;
+  }
+
+  pub fn set_max_nesting(&mut self, max: usize) {
+    self.max_nesting = max;
+  }
+
+  pub fn set_max_stack(&mut self, bytes: usize) {
+    self.max_stack = bytes;
+  }
+
+  pub fn parse(mut self) -> (Vec<Stmt>, Vec<LoxError>) {
     let mut statements = Vec::new();
+    self.stack_base = stack::position();
 
     while !self.at_end() {
-      if let Some(stmt) = self.declaration()? {
-        statements.push(stmt);
+      match self.declaration() {
+        Ok(Some(stmt)) => statements.push(stmt),
+        Ok(None) => {}
+        Err(err) => {
+          // couldn't even recover, give up on the rest
+          self.errors.push(err);
+          break;
+        }
       }
     }
 
-    Ok(statements)
+    (statements, self.errors)
   }
 
   fn declaration(&mut self) -> LoxResult<Option<Stmt>> {
-    match {
-      if self.eat(Var) {
-        self.var_declaration()
-      } else {
-        self.statement()
-      }
-    } {
+    let declaration = if self.check(&At) {
+      self.attributed_function()
+    } else if self.eat(Fun) {
+      self.function("function", Vec::new())
+    } else if self.eat(Var) {
+      self.var_declaration()
+    } else {
+      self.statement()
+    };
+
+    match declaration {
       Ok(stmt) => Ok(Some(stmt)),
-      Err(_) => {
+      Err(err) => {
+        self.errors.push(err);
         self.synchronize()?;
         Ok(None)
       }
     }
   }
 
+  fn attributed_function(&mut self) -> LoxResult<Stmt> {
+    let mut attributes = Vec::new();
+    while self.eat(At) {
+      attributes.push(self.ident(format!("Expected attribute name after '@'."))?);
+    }
+
+    self.eat_or(Fun, format!("Expected function declaration after attributes."))?;
+    self.function("function", attributes)
+  }
+
+  fn function(&mut self, kind: &str, attributes: Vec<Token>) -> LoxResult<Stmt> {
+    let name = self.ident(format!("Expected {} name.", kind))?;
+    self.eat_or(LeftParen, format!("Expected '(' after {} name.", kind))?;
+
+    let mut params = Vec::new();
+    if !self.check(&RightParen) {
+      while {
+        if params.len() >= 8 {
+          let token = self.peek().ok_or(NoneError)?.clone();
+          return Err(self.error(token, format!("Cannot have more than 8 parameters.")));
+        }
+        params.push(self.ident(format!("Expected parameter name."))?);
+        self.eat(Comma)
+      } {}
+    }
+    self.eat_or(RightParen, format!("Expected ')' after parameters."))?;
+
+    self.eat_or(LeftBrace, format!("Expected '{{' before {} body.", kind))?;
+    self.functions += 1;
+    let body = self.nested(|parser| parser.block());
+    self.functions -= 1;
+
+    Ok(Stmt::function(name, params, body?, attributes))
+  }
+
   fn var_declaration(&mut self) -> LoxResult<Stmt> {
-    let name = match self.peek()?.ty.clone() {
-      Ident(_) => self.advance()?.clone(),
-      _ => return Err(LoxError::parse(self.peek()?.clone(), format!("Expected variable name."))),
+    if self.eat(LeftParen) {
+      return self.destructure_declaration();
+    }
+
+    let name = match self.peek().ok_or(NoneError)?.ty.clone() {
+      Ident(_) => self.advance().ok_or(NoneError)?.clone(),
+      _ => return Err(LoxError::parse(self.peek().ok_or(NoneError)?.clone(), format!("Expected variable name."))),
     };
 
     let init = if self.eat(Equal) {
-      Some(self.expression()?)
+      Some(self.assignment()?)
     } else {
       None
     };
 
-    self.eat_or(Semicolon, format!("Expected ';' after variable declaration"))?;
+    self.eat_semicolon(format!("Expected ';' after variable declaration"))?;
     Ok(Stmt::var(name, init))
   }
 
+  fn destructure_declaration(&mut self) -> LoxResult<Stmt> {
+    let paren = self.previous().ok_or(NoneError)?.clone();
+    let mut names = Vec::new();
+    while {
+      names.push(self.ident(format!("Expected variable name."))?);
+      self.eat(Comma)
+    } {}
+    self.eat_or(RightParen, format!("Expected ')' after variable names."))?;
+
+    self.eat_or(Equal, format!("Expected '=' after destructuring pattern."))?;
+    let init = self.assignment()?;
+
+    self.eat_semicolon(format!("Expected ';' after variable declaration"))?;
+    Ok(Stmt::destructure(paren, names, init))
+  }
+
   fn statement(&mut self) -> LoxResult<Stmt> {
-    match () {
-      _ if self.eat(For) => self.if_statement(),
-      _ if self.eat(If) => self.if_statement(),
-      _ if self.eat(Print) => self.print_statement(),
-      _ if self.eat(While) => self.while_statement(),
-      _ if self.eat(LeftBrace) => Ok(Stmt::block(self.block()?)),
-      _ => self.expression_statement(),
-    }
+    self.nested(|parser| match () {
+      _ if parser.eat(Assert) => parser.assert_statement(),
+      _ if parser.eat(For) => parser.for_statement(),
+      _ if parser.eat(If) => parser.if_statement(),
+      _ if parser.eat(Print) => parser.print_statement(),
+      _ if parser.eat(Return) => parser.return_statement(),
+      _ if parser.eat(While) => parser.while_statement(),
+      _ if parser.eat(LeftBrace) => Ok(Stmt::block(parser.block()?)),
+      _ => parser.expression_statement(),
+    })
   }
 
   fn for_statement(&mut self) -> LoxResult<Stmt> {
-    self.eat_or(LeftParen, format!("Expect '(' after 'for'."));
+    self.eat_or(LeftParen, format!("Expect '(' after 'for'."))?;
 
     let initializer = if self.eat(Semicolon) {
       None
@@ -107,14 +222,14 @@ impl Parser {
     } else {
       self.expression()?
     };
-    self.eat_or(Semicolon, format!("Expect ';' after loop condition."));
+    self.eat_or(Semicolon, format!("Expect ';' after loop condition."))?;
 
     let increment = if self.check(&RightParen) {
       None
     } else {
       Some(self.expression()?)
     };
-    self.eat_or(Semicolon, format!("Expect ')' after for clauses."));
+    self.eat_or(RightParen, format!("Expect ')' after for clauses."))?;
 
     let mut body = self.statement()?;
 
@@ -135,9 +250,9 @@ impl Parser {
   }
 
   fn if_statement(&mut self) -> LoxResult<Stmt> {
-    self.eat_or(LeftParen, format!("Expect '(' after 'if'."));
+    self.eat_or(LeftParen, format!("Expect '(' after 'if'."))?;
     let condition = self.expression()?;
-    self.eat_or(RightParen, format!("Expect ')' after if condition."));
+    self.eat_or(RightParen, format!("Expect ')' after if condition."))?;
 
     let then_branch = self.statement()?;
     let else_branch = if self.eat(Else) {
@@ -149,18 +264,71 @@ impl Parser {
     Ok(Stmt::if_stmt(condition, then_branch, else_branch))
   }
 
+  fn return_statement(&mut self) -> LoxResult<Stmt> {
+    let keyword = self.previous().ok_or(NoneError)?.clone();
+    if self.functions == 0 {
+      return Err(self.error(keyword, format!("Cannot return from top-level code.")));
+    }
+
+    // a bare `return` right before the end of a block doesn't need its `;`
+    if self.check(&RightBrace) {
+      return Ok(Stmt::return_stmt(keyword, None));
+    }
+
+    let value = if self.check(&Semicolon) {
+      None
+    } else {
+      // `return a, b;` returns both as a tuple instead of being a comma expression
+      let mut values = vec![self.assignment()?];
+      while self.eat(Comma) {
+        values.push(self.assignment()?);
+      }
+      if values.len() == 1 {
+        values.pop()
+      } else {
+        Some(Expr::tuple(values))
+      }
+    };
+    self.eat_semicolon(format!("Expect ';' after return value."))?;
+
+    Ok(Stmt::return_stmt(keyword, value))
+  }
+
   fn while_statement(&mut self) -> LoxResult<Stmt> {
-    self.eat_or(LeftParen, format!("Expect '(' after 'while'."));
+    self.eat_or(LeftParen, format!("Expect '(' after 'while'."))?;
     let condition = self.expression()?;
-    self.eat_or(RightParen, format!("Expect ')' after while condition."));
+    self.eat_or(RightParen, format!("Expect ')' after while condition."))?;
     let body = self.statement()?;
 
     Ok(Stmt::while_stmt(condition, body))
   }
 
+  fn assert_statement(&mut self) -> LoxResult<Stmt> {
+    let keyword = self.previous().ok_or(NoneError)?.clone();
+    let condition = self.assignment()?;
+    let message = if self.eat(Comma) {
+      Some(self.assignment()?)
+    } else {
+      None
+    };
+    self.eat_semicolon(format!("Expect ';' after assertion."))?;
+
+    // `assert(cond, msg)` from when this was a native would check the (truthy) message,
+    // a comma expression that's meant as the condition needs a second pair of parens
+    if let (Expr::Grouping { expr }, None) = (&condition, &message) {
+      if let Expr::Binary { op, .. } = &**expr {
+        if op.ty == Comma {
+          return Err(self.error(keyword, format!("Use 'assert cond, msg;' instead of 'assert(cond, msg);'.")));
+        }
+      }
+    }
+
+    Ok(Stmt::assert(keyword, condition, message))
+  }
+
   fn print_statement(&mut self) -> LoxResult<Stmt> {
     let value = self.expression()?;
-    self.eat_or(Semicolon, format!("Expect ';' after value."))?;
+    self.eat_semicolon(format!("Expect ';' after value."))?;
     Ok(Stmt::print(value))
   }
 
@@ -168,7 +336,9 @@ impl Parser {
     let mut statements = Vec::new();
 
     while !self.check(&RightBrace) && !self.at_end() {
-      statements.push(self.declaration()??);
+      if let Some(stmt) = self.declaration()? {
+        statements.push(stmt);
+      }
     }
 
     self.eat_or(RightBrace, format!("Expected '}}' after block."))?;
@@ -177,26 +347,44 @@ impl Parser {
 
   fn expression_statement(&mut self) -> LoxResult<Stmt> {
     let expr = self.expression()?;
-    self.eat_or(Semicolon, format!("Expect ';' after expression"))?;
+    self.eat_semicolon(format!("Expect ';' after expression"))?;
     Ok(Stmt::expression(expr))
   }
 
   fn expression(&mut self) -> LoxResult<Expr> {
-    self.assignment()
+    self.comma()
+  }
+
+  // `a, b` evaluates both and yields `b`, call arguments are parsed one level below this
+  fn comma(&mut self) -> LoxResult<Expr> {
+    let mut expr = self.assignment()?;
+
+    while self.eat(Comma) {
+      let operator = self.previous().ok_or(NoneError)?.clone();
+      let right = self.assignment()?;
+      expr = Expr::binary(expr, operator, right);
+    }
+
+    Ok(expr)
   }
 
   fn assignment(&mut self) -> LoxResult<Expr> {
     let expr = self.or()?;
 
     if self.eat(Equal) {
-      let equals = self.previous()?.clone();
-      let value = self.assignment()?;
+      let equals = self.previous().ok_or(NoneError)?.clone();
+      // `a = b = c` recurses once per `=`
+      let value = self.nested(|parser| parser.assignment())?;
 
       match expr {
         Expr::Variable { name } => {
           return Ok(Expr::assign(name, value));
         }
-        _ => self.error(equals.clone(), format!("Invalid assignment target."))
+        // nothing to synchronize past, note it and keep going
+        _ => {
+          let err = self.error(equals.clone(), format!("Invalid assignment target."));
+          self.errors.push(err);
+        }
       };
     }
 
@@ -207,8 +395,8 @@ impl Parser {
     let mut expr = self.and()?;
 
     while self.eat(Or) {
-      let operator = self.previous()?.clone();
-      let right = self.or()?;
+      let operator = self.previous().ok_or(NoneError)?.clone();
+      let right = self.and()?;
       expr = Expr::logical(expr, operator, right);
     }
 
@@ -219,8 +407,8 @@ impl Parser {
     let mut expr = self.equality()?;
 
     while self.eat(And) {
-      let operator = self.previous()?.clone();
-      let right = self.and()?;
+      let operator = self.previous().ok_or(NoneError)?.clone();
+      let right = self.equality()?;
       expr = Expr::logical(expr, operator, right);
     }
 
@@ -230,8 +418,8 @@ impl Parser {
   fn equality(&mut self) -> LoxResult<Expr> {
     let mut expr = self.comparison()?;
 
-    while self.eat_m(&[Bang, BangEqual]) {
-      let operator = self.previous()?.clone();
+    while self.eat_m(&[BangEqual, EqualEqual]) {
+      let operator = self.previous().ok_or(NoneError)?.clone();
       let right = self.comparison()?;
       expr = Expr::binary(expr, operator, right);
     }
@@ -243,7 +431,7 @@ impl Parser {
     let mut expr = self.addition()?;
 
     while self.eat_m(&[Greater, GreaterEqual, Less, LessEqual]) {
-      let operator = self.previous()?.clone();
+      let operator = self.previous().ok_or(NoneError)?.clone();
       let right = self.addition()?;
       expr = Expr::binary(expr, operator.clone(), right);
     }
@@ -255,7 +443,7 @@ impl Parser {
     let mut expr = self.multiplication()?;
 
     while self.eat_m(&[Minus, Plus]) {
-      let operator = self.previous()?.clone();
+      let operator = self.previous().ok_or(NoneError)?.clone();
       let right = self.multiplication()?;
       expr = Expr::binary(expr, operator, right);
     }
@@ -267,7 +455,7 @@ impl Parser {
     let mut expr = self.unary()?;
 
     while self.eat_m(&[Slash, Star]) {
-      let operator = self.previous()?.clone();
+      let operator = self.previous().ok_or(NoneError)?.clone();
       let right = self.unary()?;
       expr = Expr::binary(expr, operator, right);
     }
@@ -275,14 +463,17 @@ impl Parser {
     Ok(expr)
   }
 
+  // every nested expression (groupings, arguments, operands) passes through here
   fn unary(&mut self) -> LoxResult<Expr> {
-    if self.eat_m(&[Bang, Minus]) {
-      let operator = self.previous()?.clone();
-      let right = self.unary()?;
-      Ok(Expr::unary(operator, right))
-    } else {
-      self.call()
-    }
+    self.nested(|parser| {
+      if parser.eat_m(&[Bang, Minus]) {
+        let operator = parser.previous().ok_or(NoneError)?.clone();
+        let right = parser.unary()?;
+        Ok(Expr::unary(operator, right))
+      } else {
+        parser.call()
+      }
+    })
   }
 
   fn call(&mut self) -> LoxResult<Expr> {
@@ -291,6 +482,11 @@ impl Parser {
     loop {
       if self.eat(LeftParen) {
         expr = self.finish_call(expr)?;
+      } else if self.eat(LeftBracket) {
+        let bracket = self.previous().ok_or(NoneError)?.clone();
+        let index = self.expression()?;
+        self.eat_or(RightBracket, format!("Expected ']' after index."))?;
+        expr = Expr::index(expr, bracket, index);
       } else {
         break
       }
@@ -304,36 +500,81 @@ impl Parser {
     if !self.check(&RightParen) {
       while {
         if arguments.len() >= 8 {
-          let token = self.peek()?.clone();
+          let token = self.peek().ok_or(NoneError)?.clone();
           return Err(self.error(token, format!("Cannot have more than 8 arguments.")))
         }
-        arguments.push(self.expression()?);
+        arguments.push(self.assignment()?);
         self.eat(Comma)
       } {}
     }
 
     self.eat_or(RightParen, format!("Expect ')' after arguments"))?;
-    let paren = self.previous()?.clone();
+    let paren = self.previous().ok_or(NoneError)?.clone();
 
     Ok(Expr::call(callee, paren, arguments))
   }
 
   fn primary(&mut self) -> LoxResult<Expr> {
-    match self.advance()?.ty {
-      Ident(_) => Ok(Expr::var(self.previous()?.clone())),
+    let token = self.advance().ok_or(NoneError)?;
+    match token.ty {
+      Ident(_) => Ok(Expr::var(self.previous().ok_or(NoneError)?.clone())),
       Literal(ref lit) => Ok(Expr::lit(lit.clone())),
+      Interpolation(ref head) => {
+        let mut parts = vec![Expr::lit(Lit::Str(head.clone()))];
+
+        loop {
+          parts.push(self.expression()?);
+
+          match self.advance().ok_or(NoneError)?.ty {
+            Interpolation(ref segment) => parts.push(Expr::lit(Lit::Str(segment.clone()))),
+            InterpolationEnd(ref tail) => {
+              parts.push(Expr::lit(Lit::Str(tail.clone())));
+              break;
+            }
+            _ => {
+              let tok = self.previous().ok_or(NoneError)?.clone();
+              return Err(self.error(tok, format!("Expected '}}' after interpolated expression.")));
+            }
+          }
+        }
+
+        Ok(Expr::interpolation(parts))
+      }
       LeftParen => {
+        if self.check(&RightParen) {
+          let paren = self.advance().ok_or(NoneError)?;
+          return Err(self.error(paren, format!("Empty parentheses are not a valid expression.")));
+        }
         let expr = self.expression()?;
         self.eat_or(RightParen, format!("Expected ')' after expression."))?;
         Ok(Expr::grouping(expr))
       }
       _ => {
-        let tok = self.peek()?.clone();
-        Err(self.error(tok, format!("Expected expression.")))
+        let found = Parser::describe(&token);
+        Err(self.error(token, format!("Expected expression, found {}.", found)))
+      }
+    }
+  }
+
+  fn ident(&mut self, message: String) -> LoxResult<Token> {
+    match self.peek().ok_or(NoneError)?.ty {
+      Ident(_) => Ok(self.advance().ok_or(NoneError)?),
+      _ => {
+        let tok = self.peek().ok_or(NoneError)?.clone();
+        Err(self.error(tok, message))
       }
     }
   }
 
+  fn describe(token: &Token) -> String {
+    match token.ty {
+      Ident(_) => format!("{} '{}'", token.ty, token.raw),
+      Literal(Lit::Num(_)) => format!("number {}", token.raw),
+      Literal(Lit::Str(_)) | Interpolation(_) | InterpolationEnd(_) => format!("string {}", token.raw),
+      _ => token.ty.to_string(),
+    }
+  }
+
   fn eat(&mut self, tt: TokenType) -> bool {
     if self.check(&tt) {
       self.advance();
@@ -357,23 +598,44 @@ impl Parser {
     if self.eat(tt) {
       Ok(())
     } else {
-      let tok = self.peek()?.clone();
+      let tok = self.peek().ok_or(NoneError)?.clone();
       Err(self.error(tok, message))
     }
   }
 
+  fn eat_semicolon(&mut self, message: String) -> Result<(), LoxError> {
+    if self.relaxed_semicolons && self.check(&RightBrace) || self.trailing_expression && self.at_end() {
+      return Ok(());
+    }
+    self.eat_or(Semicolon, message)
+  }
+
+  fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> LoxResult<T>) -> LoxResult<T> {
+    if self.nesting >= self.max_nesting {
+      let token = self.peek().ok_or(NoneError)?.clone();
+      return Err(self.error(token, format!("Too deeply nested, the limit is {}.", self.max_nesting)));
+    }
+    if stack::used_since(self.stack_base) > self.max_stack {
+      let token = self.peek().ok_or(NoneError)?.clone();
+      return Err(self.error(token, format!("Too deeply nested, ran out of stack space.")));
+    }
+
+    self.nesting += 1;
+    let res = parse(self);
+    self.nesting -= 1;
+    res
+  }
+
   fn error(&mut self, token: Token, message: String) -> LoxError {
-    let err = LoxError::parse(token, message);
-    Lox::report(err.clone());
-    err
+    LoxError::parse(token, message)
   }
 
   fn check(&mut self, tt: &TokenType) -> bool {
-    !self.at_end() && self.peek().map_or(false, |token| &token.ty == tt)
+    !self.at_end() && self.peek().is_some_and(|token| &token.ty == tt)
   }
 
   fn advance(&mut self) -> Option<Token> {
-    let token = self.peek().map(|tok| tok.clone());
+    let token = self.peek().cloned();
     if !self.at_end() {
       self.current += 1;
     }
@@ -381,7 +643,7 @@ impl Parser {
   }
 
   fn at_end(&mut self) -> bool {
-    self.peek().map_or(false, |token| token.ty == TokenType::EOF)
+    self.peek().is_some_and(|token| token.ty == TokenType::EOF)
   }
 
   fn peek(&mut self) -> Option<&Token> {
@@ -396,12 +658,12 @@ impl Parser {
     self.advance();
 
     while !self.at_end() {
-      if self.previous()?.ty == Semicolon {
+      if self.previous().ok_or(NoneError)?.ty == Semicolon {
         return Ok(());
       }
 
-      match self.peek()?.ty {
-        Class | Fun | Var | For | If | While | Print | Return => {
+      match self.peek().ok_or(NoneError)?.ty {
+        Class | Fun | At | Var | For | If | While | Print | Return | Assert => {
           return Ok(());
         }
         _ => {
@@ -410,6 +672,71 @@ impl Parser {
       }
     }
 
-    return Ok(());
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::scanner::Scanner;
+
+  fn parse(source: &str) -> (Vec<Stmt>, Vec<LoxError>) {
+    let (tokens, errors) = Scanner::new(source.to_string()).scan_tokens();
+    assert!(errors.is_empty());
+    Parser::new(tokens).parse()
+  }
+
+  fn messages(errors: &[LoxError]) -> Vec<String> {
+    errors.iter().map(|err| err.to_string()).collect()
+  }
+
+  #[test]
+  fn long_logical_chains_dont_nest() {
+    let source = format!("print true{};", " or false and true".repeat(1000));
+    let (statements, errors) = parse(&source);
+    assert_eq!(messages(&errors), Vec::<String>::new());
+    assert_eq!(statements.len(), 1);
+  }
+
+  #[test]
+  fn chained_assignment_is_capped() {
+    let source = format!("var x; {} 1;", "x =".repeat(100));
+    let (tokens, _) = Scanner::new(source).scan_tokens();
+    let mut parser = Parser::new(tokens);
+    parser.set_max_nesting(20);
+    let (_, errors) = parser.parse();
+    assert_eq!(errors.len(), 1);
+    assert!(messages(&errors)[0].contains("Too deeply nested, the limit is 20."), "{:?}", messages(&errors));
+  }
+
+  #[test]
+  fn deeply_nested_parentheses_run_out_of_stack_cleanly() {
+    // each level is tens of KB in a debug build, this has to error long before the test thread overflows
+    let source = format!("print {}1{};", "(".repeat(1000), ")".repeat(1000));
+    let (_, errors) = parse(&source);
+    assert_eq!(errors.len(), 1);
+    assert!(messages(&errors)[0].contains("Too deeply nested, ran out of stack space."), "{:?}", messages(&errors));
+
+    let source = format!("print {}1{};", "(".repeat(10), ")".repeat(10));
+    assert!(parse(&source).1.is_empty());
+  }
+
+  #[test]
+  fn missing_expression_names_what_was_found() {
+    let (_, errors) = parse("print 1 + );");
+    assert_eq!(messages(&errors), vec!["[Line: 1:11] Error at ')': Expected expression, found ')'."]);
+  }
+
+  #[test]
+  fn empty_parentheses_are_not_an_expression() {
+    let (_, errors) = parse("print ();");
+    assert_eq!(messages(&errors), vec!["[Line: 1:8] Error at ')': Empty parentheses are not a valid expression."]);
+  }
+
+  #[test]
+  fn assert_call_syntax_is_rejected() {
+    let (_, errors) = parse("assert(false, \"x\"); print \"passed silently\";");
+    assert_eq!(messages(&errors), vec!["[Line: 1:1] Error at 'assert': Use 'assert cond, msg;' instead of 'assert(cond, msg);'."]);
   }
 }
@@ -10,3 +10,106 @@ impl std::fmt::Display for Pos {
     write!(f, "{}:{}", self.line, self.ch)
   }
 }
+
+// Maps between byte offsets into a source string and line/column positions.
+// Lines and columns are 1-based, columns count chars, not bytes.
+pub struct LineIndex<'a> {
+  source: &'a str,
+  // byte offset and char index every line starts at
+  line_starts: Vec<(usize, usize)>,
+}
+
+impl<'a> LineIndex<'a> {
+  pub fn new(source: &'a str) -> Self {
+    let mut line_starts = vec![(0, 0)];
+
+    for (idx, (offset, c)) in source.char_indices().enumerate() {
+      if c == '\n' {
+        line_starts.push((offset + 1, idx + 1));
+      }
+    }
+
+    LineIndex {
+      source,
+      line_starts,
+    }
+  }
+
+  pub fn pos(&self, offset: usize) -> Option<Pos> {
+    if offset > self.source.len() || !self.source.is_char_boundary(offset) {
+      return None;
+    }
+
+    let line = match self.line_starts.binary_search_by_key(&offset, |&(start, _)| start) {
+      Ok(line) => line,
+      Err(next) => next - 1,
+    };
+    let (line_offset, line_idx) = self.line_starts[line];
+    let ch = self.source[line_offset..offset].chars().count();
+
+    Some(Pos {
+      line: line + 1,
+      ch: ch + 1,
+      idx: line_idx + ch,
+    })
+  }
+
+  pub fn offset(&self, pos: Pos) -> Option<usize> {
+    let &(line_offset, _) = self.line_starts.get(pos.line.checked_sub(1)?)?;
+    let line_end = self.line_starts.get(pos.line).map_or(self.source.len(), |&(start, _)| start);
+    let line = &self.source[line_offset..line_end];
+
+    let col = pos.ch.checked_sub(1)?;
+    match line.char_indices().nth(col) {
+      Some((offset, _)) => Some(line_offset + offset),
+      // one past the last char of the final line is still a valid position
+      None if col == line.chars().count() && line_end == self.source.len() => Some(self.source.len()),
+      None => None,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SOURCE: &str = "var a;\nprint \"é\" + b;\n";
+
+  fn pos(line: usize, ch: usize, idx: usize) -> Pos {
+    Pos { line, ch, idx }
+  }
+
+  #[test]
+  fn line_start() {
+    let index = LineIndex::new(SOURCE);
+    assert_eq!(index.pos(7), Some(pos(2, 1, 7)));
+    assert_eq!(index.offset(pos(2, 1, 7)), Some(7));
+  }
+
+  #[test]
+  fn newline_belongs_to_the_line_it_ends() {
+    let index = LineIndex::new(SOURCE);
+    assert_eq!(index.pos(6), Some(pos(1, 7, 6)));
+    assert_eq!(index.offset(pos(1, 7, 6)), Some(6));
+  }
+
+  #[test]
+  fn columns_count_chars_not_bytes() {
+    let index = LineIndex::new(SOURCE);
+    // `é` is two bytes, the `"` after it starts at byte 16
+    assert_eq!(index.pos(14), Some(pos(2, 8, 14)));
+    assert_eq!(index.pos(16), Some(pos(2, 9, 15)));
+    assert_eq!(index.offset(pos(2, 9, 15)), Some(16));
+    assert_eq!(index.pos(15), None);
+  }
+
+  #[test]
+  fn one_past_the_end() {
+    let index = LineIndex::new(SOURCE);
+    let end = SOURCE.len();
+    assert_eq!(index.pos(end), Some(pos(3, 1, 22)));
+    assert_eq!(index.offset(pos(3, 1, 22)), Some(end));
+    assert_eq!(index.pos(end + 1), None);
+    assert_eq!(index.offset(pos(3, 2, 23)), None);
+  }
+}
@@ -0,0 +1,180 @@
+use crate::{
+  err::LoxError,
+  err::LoxResult,
+  interpreter::Interpreter,
+  lit::Lit,
+};
+
+pub fn define(interpreter: &mut Interpreter) {
+  use std::f64;
+
+  interpreter.define("PI", Lit::Num(f64::consts::PI));
+  interpreter.define("E", Lit::Num(f64::consts::E));
+  interpreter.define("INFINITY", Lit::Num(f64::INFINITY));
+  interpreter.define("NAN", Lit::Num(f64::NAN));
+
+  interpreter.define_native("clock", 0, clock);
+  interpreter.define_native("round_even", 1, round_even);
+  interpreter.define_native("hex", 1, hex);
+  interpreter.define_native("oct", 1, oct);
+  interpreter.define_native("bin", 1, bin);
+  interpreter.define_native("env", 1, env);
+  interpreter.define_native("eval", 1, eval);
+  interpreter.define_native("bool", 1, bool);
+  // `print` is a statement, this is the same thing as a function value
+  interpreter.define_native("println", 1, println);
+}
+
+fn number(lit: &Lit) -> LoxResult<f64> {
+  match lit {
+    Lit::Num(num) => Ok(*num),
+    _ => Err(LoxError::other(format!("Expected a number but got {}.", lit))),
+  }
+}
+
+// formats an integral number in another base, negative numbers get a leading '-'
+fn radix(name: &str, arg: &Lit, format: fn(u64) -> String) -> LoxResult<Lit> {
+  let num = number(arg)?;
+  if num.fract() != 0.0 {
+    return Err(LoxError::other(format!("{}() expects an integer but got {}.", name, num)));
+  }
+  // `as u64` would saturate, 2^64 itself is already one too many
+  if num.abs() >= u64::MAX as f64 {
+    return Err(LoxError::other(format!("{}() can't format {}, it's too large.", name, num)));
+  }
+
+  let sign = if num < 0.0 { "-" } else { "" };
+  Ok(Lit::Str(format!("{}{}", sign, format(num.abs() as u64))))
+}
+
+fn hex(_: &mut Interpreter, args: Vec<Lit>) -> LoxResult<Lit> {
+  radix("hex", &args[0], |num| format!("{:x}", num))
+}
+
+fn oct(_: &mut Interpreter, args: Vec<Lit>) -> LoxResult<Lit> {
+  radix("oct", &args[0], |num| format!("{:o}", num))
+}
+
+fn bin(_: &mut Interpreter, args: Vec<Lit>) -> LoxResult<Lit> {
+  radix("bin", &args[0], |num| format!("{:b}", num))
+}
+
+// runs a snippet of Lox in the calling scope and yields its last expression's value
+fn eval(interpreter: &mut Interpreter, args: Vec<Lit>) -> LoxResult<Lit> {
+  match args[0] {
+    Lit::Str(ref source) => interpreter.eval_str(source.clone()),
+    ref other => Err(LoxError::other(format!("Expected a string but got {}.", other))),
+  }
+}
+
+// only nil and false are falsy, everything else (0, "", ...) is true
+fn bool(_: &mut Interpreter, args: Vec<Lit>) -> LoxResult<Lit> {
+  Ok(Lit::Bool(args[0].is_truthy()))
+}
+
+fn env(interpreter: &mut Interpreter, args: Vec<Lit>) -> LoxResult<Lit> {
+  match args[0] {
+    Lit::Str(ref name) => Ok(interpreter.host_var(name).map_or(Lit::Nil, Lit::Str)),
+    ref other => Err(LoxError::other(format!("Expected a string but got {}.", other))),
+  }
+}
+
+fn println(interpreter: &mut Interpreter, args: Vec<Lit>) -> LoxResult<Lit> {
+  let line = interpreter.stringify(&args[0]);
+  interpreter.print(&line);
+  Ok(Lit::Nil)
+}
+
+fn clock(_: &mut Interpreter, _: Vec<Lit>) -> LoxResult<Lit> {
+  use std::time::{SystemTime, UNIX_EPOCH};
+
+  Ok(Lit::Num(SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards").as_millis() as f64))
+}
+
+// Rounds halfway cases to the nearest even number (2.5 -> 2, 3.5 -> 4),
+// unlike f64::round which always rounds them away from zero.
+fn round_even(_: &mut Interpreter, args: Vec<Lit>) -> LoxResult<Lit> {
+  let num = number(&args[0])?;
+  let rounded = num.round();
+
+  if (num - num.trunc()).abs() == 0.5 && rounded % 2.0 != 0.0 {
+    Ok(Lit::Num(rounded - num.signum()))
+  } else {
+    Ok(Lit::Num(rounded))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::lit::NativeFn;
+
+  fn call(native: NativeFn, args: Vec<Lit>) -> LoxResult<Lit> {
+    native(&mut Interpreter::new(), args)
+  }
+
+  fn run(source: &str) -> String {
+    let mut interpreter = Interpreter::new();
+    interpreter.capture_output();
+    if let Err(err) = interpreter.eval_str(source.to_string()) {
+      panic!("{}", err);
+    }
+    interpreter.take_output()
+  }
+
+  fn num(result: LoxResult<Lit>) -> Option<f64> {
+    match result {
+      Ok(Lit::Num(num)) => Some(num),
+      _ => None,
+    }
+  }
+
+  fn string(result: LoxResult<Lit>) -> Option<String> {
+    match result {
+      Ok(Lit::Str(s)) => Some(s),
+      _ => None,
+    }
+  }
+
+  #[test]
+  fn round_even_breaks_ties_to_even() {
+    let round = |n: f64| num(call(round_even, vec![Lit::Num(n)]));
+    assert_eq!(round(2.5), Some(2.0));
+    assert_eq!(round(3.5), Some(4.0));
+    assert_eq!(round(-2.5), Some(-2.0));
+    assert_eq!(round(-3.5), Some(-4.0));
+    assert_eq!(round(2.6), Some(3.0));
+  }
+
+  #[test]
+  fn radix_formats_integers() {
+    assert_eq!(string(call(hex, vec![Lit::Num(255.0)])).as_deref(), Some("ff"));
+    assert_eq!(string(call(bin, vec![Lit::Num(5.0)])).as_deref(), Some("101"));
+    assert_eq!(string(call(oct, vec![Lit::Num(-8.0)])).as_deref(), Some("-10"));
+  }
+
+  #[test]
+  fn radix_rejects_what_doesnt_fit() {
+    assert!(call(hex, vec![Lit::Num(1e20)]).is_err());
+    assert!(call(hex, vec![Lit::Num(2f64.powi(64))]).is_err());
+    assert!(call(hex, vec![Lit::Num(1.5)]).is_err());
+    assert_eq!(string(call(hex, vec![Lit::Num(2f64.powi(63))])).as_deref(), Some("8000000000000000"));
+  }
+
+  #[test]
+  fn constants() {
+    assert_eq!(run("print PI;"), format!("{}\n", std::f64::consts::PI));
+    assert_eq!(run("print E; print INFINITY; print -INFINITY;"), format!("{}\ninf\n-inf\n", std::f64::consts::E));
+    assert_eq!(run("print NAN == NAN;"), "false\n");
+  }
+
+  #[test]
+  fn bool_uses_lox_truthiness() {
+    assert_eq!(run("print bool(0); print bool(\"\"); print bool(nil); print bool(false);"), "true\ntrue\nfalse\nfalse\n");
+  }
+
+  #[test]
+  fn println_is_a_value() {
+    assert_eq!(run("fun ap(f, x) { f(x); } ap(println, \"hi\"); ap(println, 2);"), "hi\n2\n");
+  }
+}
@@ -0,0 +1,79 @@
+use std::{
+  collections::HashMap,
+  fmt::{Display, Formatter, Error},
+  time::Duration,
+};
+
+// Counts how often each Stmt/Expr variant runs and how long each
+// function spends in calls (including nested calls).
+pub struct Profiler {
+  counts: HashMap<&'static str, usize>,
+  calls: HashMap<String, (usize, Duration)>,
+}
+
+impl Default for Profiler {
+  fn default() -> Self {
+    Profiler::new()
+  }
+}
+
+impl Profiler {
+  pub fn new() -> Self {
+    Profiler {
+      counts: HashMap::new(),
+      calls: HashMap::new(),
+    }
+  }
+
+  pub fn count(&mut self, name: &'static str) {
+    *self.counts.entry(name).or_insert(0) += 1;
+  }
+
+  pub fn count_of(&self, name: &str) -> usize {
+    self.counts.get(name).copied().unwrap_or(0)
+  }
+
+  pub fn record_call(&mut self, name: &str, elapsed: Duration) {
+    let entry = self.calls.entry(name.to_string()).or_insert((0, Duration::new(0, 0)));
+    entry.0 += 1;
+    entry.1 += elapsed;
+  }
+}
+
+impl Display for Profiler {
+  fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+    let mut counts = self.counts.iter().collect::<Vec<_>>();
+    counts.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+
+    writeln!(f, "evaluations:")?;
+    for (name, count) in counts {
+      writeln!(f, "  {:<24} {}", name, count)?;
+    }
+
+    let mut calls = self.calls.iter().collect::<Vec<_>>();
+    calls.sort_by(|a, b| (b.1).1.cmp(&(a.1).1).then(a.0.cmp(b.0)));
+
+    writeln!(f, "functions:")?;
+    for (name, (count, time)) in calls {
+      writeln!(f, "  {:<24} {} calls, {}us", name, count, time.as_micros())?;
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn counts_add_up() {
+    let mut profiler = Profiler::new();
+    profiler.count("Stmt::Print");
+    profiler.count("Stmt::Print");
+    profiler.count("Expr::Literal");
+    assert_eq!(profiler.count_of("Stmt::Print"), 2);
+    assert_eq!(profiler.count_of("Expr::Literal"), 1);
+    assert_eq!(profiler.count_of("Stmt::While"), 0);
+  }
+}
@@ -0,0 +1,45 @@
+use crate::{
+  lit::Lit,
+  pos::Pos,
+  scanner::Token,
+};
+
+// A variable being defined or assigned, `old` is None when it didn't exist in that scope yet.
+pub struct WriteEvent {
+  pub name: String,
+  pub old: Option<Lit>,
+  pub new: Lit,
+  pub pos: Pos,
+}
+
+// Keeps every variable write in the order they happened, for stepping back through a run.
+pub struct Recorder {
+  events: Vec<WriteEvent>,
+}
+
+impl Default for Recorder {
+  fn default() -> Self {
+    Recorder::new()
+  }
+}
+
+impl Recorder {
+  pub fn new() -> Self {
+    Recorder {
+      events: Vec::new(),
+    }
+  }
+
+  pub fn record(&mut self, name: &Token, old: Option<Lit>, new: Lit) {
+    self.events.push(WriteEvent {
+      name: name.raw.clone(),
+      old,
+      new,
+      pos: name.pos,
+    });
+  }
+
+  pub fn events(&self) -> &[WriteEvent] {
+    &self.events
+  }
+}
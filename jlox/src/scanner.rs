@@ -12,6 +12,8 @@ pub enum TokenType {
   RightParen,
   LeftBrace,
   RightBrace,
+  LeftBracket,
+  RightBracket,
   Comma,
   Dot,
   Minus,
@@ -19,6 +21,7 @@ pub enum TokenType {
   Semicolon,
   Slash,
   Star,
+  At,
 
   // One or two character tokens.
   Bang,
@@ -29,13 +32,21 @@ pub enum TokenType {
   GreaterEqual,
   Less,
   LessEqual,
+  Arrow,
+  DotDot,
+  DotDotEqual,
+  DotDotDot,
 
   // Literals.
   Literal(Lit),
   Ident(String),
+  // String segment followed by `${`, and the segment after the last `}`.
+  Interpolation(String),
+  InterpolationEnd(String),
 
   // Keywords.
   And,
+  Assert,
   Class,
   Else,
   Fun,
@@ -52,6 +63,60 @@ pub enum TokenType {
   EOF,
 }
 
+impl ::std::fmt::Display for TokenType {
+  fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+    use self::TokenType::*;
+    let name = match self {
+      LeftParen => "'('",
+      RightParen => "')'",
+      LeftBrace => "'{'",
+      RightBrace => "'}'",
+      LeftBracket => "'['",
+      RightBracket => "']'",
+      Comma => "','",
+      Dot => "'.'",
+      Minus => "'-'",
+      Plus => "'+'",
+      Semicolon => "';'",
+      Slash => "'/'",
+      Star => "'*'",
+      At => "'@'",
+      Bang => "'!'",
+      BangEqual => "'!='",
+      Equal => "'='",
+      EqualEqual => "'=='",
+      Greater => "'>'",
+      GreaterEqual => "'>='",
+      Less => "'<'",
+      LessEqual => "'<='",
+      Arrow => "'->'",
+      DotDot => "'..'",
+      DotDotEqual => "'..='",
+      DotDotDot => "'...'",
+      Literal(lit) => return write!(f, "{}", lit),
+      Ident(_) => "identifier",
+      Interpolation(_) => "interpolated string",
+      InterpolationEnd(_) => "end of interpolated string",
+      And => "'and'",
+      Assert => "'assert'",
+      Class => "'class'",
+      Else => "'else'",
+      Fun => "'fun'",
+      For => "'for'",
+      If => "'if'",
+      Or => "'or'",
+      Print => "'print'",
+      Return => "'return'",
+      Super => "'super'",
+      This => "'this'",
+      Var => "'var'",
+      While => "'while'",
+      EOF => "end of input",
+    };
+    write!(f, "{}", name)
+  }
+}
+
 #[derive(Clone, PartialEq)]
 pub struct Token {
   pub ty: TokenType,
@@ -72,25 +137,39 @@ impl Token {
 #[derive(Debug)]
 struct CharStream {
   chars: Vec<char>,
+  // position of the next char
   pos: Pos,
+  // position of the char we just consumed
+  last: Pos,
+  // with ascii-only source every char is one byte, so `pos.idx` is also a byte offset
+  ascii: bool,
 }
 
 impl CharStream {
-  pub fn new(source: &String) -> Self {
+  pub fn new(source: &str) -> Self {
+    // the first char sits at 1:1, there's nothing consumed yet
+    let start = Pos { line: 1, ch: 1, idx: 0 };
     CharStream {
       chars: source.chars().collect(),
-      pos: Pos { line: 1, ch: 0, idx: 0 },
+      pos: start,
+      last: start,
+      ascii: source.is_ascii(),
     }
   }
 
   pub fn next(&mut self) -> Option<char> {
+    let c = *self.chars.get(self.pos.idx)?;
+
+    self.last = self.pos;
     self.pos.idx += 1;
-    self.pos.ch += 1;
-    if self.pos.idx > 1 && Some('\n') == self.chars.get(self.pos.idx - 2).map(|c| *c) {
+    if c == '\n' {
       self.pos.line += 1;
-      self.pos.ch = 0;
+      self.pos.ch = 1;
+    } else {
+      self.pos.ch += 1;
     }
-    Some(*self.chars.get(self.pos.idx - 1)?)
+
+    Some(c)
   }
 
   pub fn peek(&self) -> char {
@@ -105,8 +184,16 @@ impl CharStream {
     self.pos
   }
 
+  pub fn last_pos(&self) -> Pos {
+    self.last
+  }
+
+  pub fn is_ascii(&self) -> bool {
+    self.ascii
+  }
+
   pub fn is_eof(&self) -> bool {
-    None == self.chars.get(self.pos.idx - 1)
+    self.pos.idx >= self.chars.len()
   }
 
   pub fn str_from(&self, start: &Pos) -> String {
@@ -114,7 +201,7 @@ impl CharStream {
   }
 
   pub fn str_from_to(&self, start: &Pos, end: &Pos) -> String {
-    self.chars.iter().map(|c| *c).skip(start.idx).take(end.idx - start.idx).collect()
+    self.chars.iter().copied().skip(start.idx).take(end.idx - start.idx).collect()
   }
 }
 
@@ -123,6 +210,9 @@ pub struct Scanner {
   tokens: Vec<Token>,
   stream: CharStream,
   start: Pos,
+  // where every `${` we're currently inside of starts, and the brace depth inside it
+  interpolation: Vec<(Pos, usize)>,
+  errors: Vec<LoxError>,
 }
 
 impl Scanner {
@@ -134,16 +224,26 @@ impl Scanner {
       tokens: Vec::new(),
       start: stream.pos(),
       stream,
+      interpolation: Vec::new(),
+      errors: Vec::new(),
     }
   }
 
-  pub fn scan_tokens(mut self) -> LoxResult<Vec<Token>> {
+  pub fn scan_tokens(mut self) -> (Vec<Token>, Vec<LoxError>) {
     while let Some(c) = self.stream.next() {
-      self.scan_token(c)?;
+      if let Err(err) = self.scan_token(c) {
+        // drop what we couldn't make sense of and carry on with the next char
+        self.errors.push(err);
+        self.start = self.stream.pos();
+      }
+    }
+
+    if let Some(&(start, _)) = self.interpolation.first() {
+      self.errors.push(LoxError::lex(start, format!("Unterminated string interpolation.")));
     }
 
     self.tokens.push(Token::new(TokenType::EOF, String::new(), self.stream.pos()));
-    Ok(self.tokens)
+    (self.tokens, self.errors)
   }
 
   fn scan_token(&mut self, c: char) -> LoxResult<()> {
@@ -151,14 +251,51 @@ impl Scanner {
     match c {
       '(' => self.add_token(LeftParen),
       ')' => self.add_token(RightParen),
-      '{' => self.add_token(LeftBrace),
-      '}' => self.add_token(RightBrace),
+      '{' => {
+        if let Some((_, depth)) = self.interpolation.last_mut() {
+          *depth += 1;
+        }
+        self.add_token(LeftBrace);
+      }
+      '}' => {
+        match self.interpolation.last_mut() {
+          Some((_, 0)) => {
+            self.interpolation.pop();
+            self.string(true)?;
+          }
+          Some((_, depth)) => {
+            *depth -= 1;
+            self.add_token(RightBrace);
+          }
+          None => self.add_token(RightBrace),
+        }
+      }
+      '[' => self.add_token(LeftBracket),
+      ']' => self.add_token(RightBracket),
       ',' => self.add_token(Comma),
-      '.' => self.add_token(Dot),
-      '-' => self.add_token(Minus),
+      '.' => {
+        // longest match wins: `...`, `..=`, `..`, `.`
+        let tt = if self.match_next('.') {
+          if self.match_next('.') {
+            DotDotDot
+          } else if self.match_next('=') {
+            DotDotEqual
+          } else {
+            DotDot
+          }
+        } else {
+          Dot
+        };
+        self.add_token(tt);
+      }
+      '-' => {
+        let tt = if self.match_next('>') { Arrow } else { Minus };
+        self.add_token(tt);
+      }
       '+' => self.add_token(Plus),
       ';' => self.add_token(Semicolon),
       '*' => self.add_token(Star),
+      '@' => self.add_token(At),
       '!' => {
         let tt = if self.match_next('=') { BangEqual } else { Bang };
         self.add_token(tt);
@@ -181,68 +318,137 @@ impl Scanner {
           while self.stream.peek() != '\n' && !self.stream.is_eof() {
             self.stream.next();
           }
+        } else if self.match_next('*') {
+          self.block_comment()?;
         } else {
           self.add_token(Slash);
         }
       }
-      '"' => self.string()?,
+      '"' => self.string(false)?,
       // ignore whitespace
       ' ' | '\r' | '\t' | '\n' => {
         self.start = self.stream.pos();
       }
-      c if c.is_digit(10) => self.number()?,
-      c if c.is_alphanumeric() => self.identifier()?,
+      c if c.is_ascii_digit() => self.number()?,
+      c if self.is_alphanumeric(c) => self.identifier()?,
       c => {
-        return Err(LoxError::lex(self.stream.pos(), format!("Unexpected character: {:?}", c)));
+        return Err(LoxError::lex(self.stream.last_pos(), format!("Unexpected character: {:?}", c)));
       }
     }
     Ok(())
   }
 
-  fn string(&mut self) -> LoxResult<()> {
-    while !self.match_next('"') && !self.stream.is_eof() {
-      self.stream.next();
+  // `/* ... */`, these nest
+  fn block_comment(&mut self) -> LoxResult<()> {
+    let mut depth = 1;
+
+    while depth > 0 {
+      match self.stream.next() {
+        Some('/') if self.match_next('*') => depth += 1,
+        Some('*') if self.match_next('/') => depth -= 1,
+        Some(_) => {}
+        None => return Err(LoxError::lex(self.start, format!("Unterminated block comment."))),
+      }
     }
 
-    if self.stream.is_eof() {
-      return Err(LoxError::lex(self.stream.pos(), format!("Unterminated string.")));
+    self.start = self.stream.pos();
+    Ok(())
+  }
+
+  fn string(&mut self, continuation: bool) -> LoxResult<()> {
+    let mut value = String::new();
+    // a bad escape doesn't end the string, report it once we're past the closing quote
+    let mut error = None;
+
+    loop {
+      match self.stream.next() {
+        Some('"') => break,
+        Some('\\') => {
+          let escape = self.stream.last_pos();
+          match self.stream.next() {
+            Some('n') => value.push('\n'),
+            Some('t') => value.push('\t'),
+            Some('r') => value.push('\r'),
+            Some(c @ '\\') | Some(c @ '"') | Some(c @ '$') => value.push(c),
+            Some(c) => {
+              if error.is_none() {
+                error = Some(LoxError::lex(escape, format!("Unknown escape sequence '\\{}'.", c)));
+              }
+            }
+            None => return Err(LoxError::lex(self.start, format!("Unterminated string."))),
+          }
+        }
+        Some('$') if self.stream.peek() == '{' => {
+          let start = self.stream.last_pos();
+          self.stream.next();
+          // the rest of the string gets scanned once we hit the matching '}'
+          self.interpolation.push((start, 0));
+          self.add_token(TokenType::Interpolation(value));
+          return error.map_or(Ok(()), Err);
+        }
+        Some(c) => value.push(c),
+        None => return Err(LoxError::lex(self.start, format!("Unterminated string."))),
+      }
     }
 
-    let mut new_start = self.start;
-    new_start.idx += 1;
-    let mut new_end = self.stream.pos();
-    new_end.idx -= 1;
+    if let Some(error) = error {
+      return Err(error);
+    }
 
-    self.add_token(TokenType::Literal(Lit::Str(self.stream.str_from_to(&new_start, &new_end))));
+    if continuation {
+      self.add_token(TokenType::InterpolationEnd(value));
+    } else {
+      self.add_token(TokenType::Literal(Lit::Str(value)));
+    }
 
     Ok(())
   }
 
   fn number(&mut self) -> LoxResult<()> {
-    while self.stream.peek().is_digit(10) { self.stream.next(); }
+    self.digits()?;
 
-    if self.stream.peek() == '.' && (if self.stream.peek_n(2).is_digit(10) { true } else { false }) {
+    if self.stream.peek() == '.' && self.stream.peek_n(1).is_ascii_digit() {
       self.stream.next();
       self.stream.next();
-      while self.stream.peek().is_digit(10) { self.stream.next(); }
+      self.digits()?;
     }
 
-    self.add_token(TokenType::Literal(Lit::Num(self.stream.str_from(&self.start).parse().unwrap())));
+    let value = self.str_from(&self.start).replace('_', "").parse().unwrap();
+    self.add_token(TokenType::Literal(Lit::Num(value)));
 
     Ok(())
   }
 
+  // eats the rest of a run of digits, `_` is only allowed as a separator between two of them
+  fn digits(&mut self) -> LoxResult<()> {
+    loop {
+      match self.stream.peek() {
+        c if c.is_ascii_digit() => {
+          self.stream.next();
+        }
+        '_' => {
+          self.stream.next();
+          if !self.stream.peek().is_ascii_digit() {
+            return Err(LoxError::lex(self.stream.last_pos(), format!("Numeric separator '_' must be between two digits.")));
+          }
+        }
+        _ => return Ok(()),
+      }
+    }
+  }
+
   fn identifier(&mut self) -> LoxResult<()> {
-    while self.stream.peek().is_alphanumeric() {
+    while self.is_alphanumeric(self.stream.peek()) {
       self.stream.next();
     }
 
-    let ident = self.stream.str_from(&self.start);
+    let ident = self.str_from(&self.start);
 
     use self::TokenType::*;
 
     self.add_token(match ident.as_ref() {
       "and" => And,
+      "assert" => Assert,
       "class" => Class,
       "else" => Else,
       "false" => Literal(Lit::Bool(false)),
@@ -265,11 +471,27 @@ impl Scanner {
   }
 
   fn add_token(&mut self, tt: TokenType) {
-    let text = self.stream.str_from(&self.start);
+    let text = self.str_from(&self.start);
     self.tokens.push(Token::new(tt, text, self.start));
     self.start = self.stream.pos();
   }
 
+  fn str_from(&self, start: &Pos) -> String {
+    if self.stream.is_ascii() {
+      self.source[start.idx..self.stream.pos().idx].to_string()
+    } else {
+      self.stream.str_from(start)
+    }
+  }
+
+  fn is_alphanumeric(&self, c: char) -> bool {
+    if self.stream.is_ascii() {
+      c.is_ascii_alphanumeric() || c == '_'
+    } else {
+      c.is_alphanumeric() || c == '_'
+    }
+  }
+
   fn match_next(&mut self, expected: char) -> bool {
     if self.stream.peek() == expected {
       self.stream.next();
@@ -279,3 +501,189 @@ impl Scanner {
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn scan(source: &str) -> (Vec<Token>, Vec<LoxError>) {
+    Scanner::new(source.to_string()).scan_tokens()
+  }
+
+  fn raw(tokens: &[Token]) -> Vec<&str> {
+    tokens.iter().map(|token| token.raw.as_str()).collect()
+  }
+
+  fn messages(errors: &[LoxError]) -> Vec<String> {
+    errors.iter().map(|err| err.to_string()).collect()
+  }
+
+  fn types(source: &str) -> Vec<TokenType> {
+    let (tokens, errors) = scan(source);
+    assert!(errors.is_empty());
+    tokens.into_iter().map(|token| token.ty).collect()
+  }
+
+  #[test]
+  fn positions_after_newlines() {
+    let (tokens, _) = scan("\n\nx");
+    assert_eq!(tokens[0].pos, Pos { line: 3, ch: 1, idx: 2 });
+
+    let (tokens, _) = scan("x\ny");
+    assert_eq!(tokens[0].pos, Pos { line: 1, ch: 1, idx: 0 });
+    assert_eq!(tokens[1].pos, Pos { line: 2, ch: 1, idx: 2 });
+  }
+
+  #[test]
+  fn braces_inside_interpolation_dont_end_it() {
+    let (tokens, errors) = scan("\"a${ {} }b\"");
+    assert!(errors.is_empty());
+    let types = tokens.into_iter().map(|token| token.ty).collect::<Vec<_>>();
+    assert!(types == vec![
+      TokenType::Interpolation("a".to_string()),
+      TokenType::LeftBrace,
+      TokenType::RightBrace,
+      TokenType::InterpolationEnd("b".to_string()),
+      TokenType::EOF,
+    ]);
+  }
+
+  #[test]
+  fn ascii_fast_path_matches_the_unicode_path() {
+    let source = "fun add_1(a) { return a + 1_000.5; }\nvar x = add_1(2) >= 3 and \"s\" != nil; // done";
+    let (ascii, ascii_errors) = scan(source);
+    // a trailing non-ascii comment takes the slow path without adding tokens
+    let (unicode, unicode_errors) = scan(&format!("{} é", source));
+    assert!(ascii_errors.is_empty() && unicode_errors.is_empty());
+    assert_eq!(ascii.len(), unicode.len());
+    for (a, b) in ascii.iter().zip(&unicode).take(ascii.len() - 1) {
+      assert!(a == b, "{} != {}", a.raw, b.raw);
+    }
+  }
+
+  #[test]
+  fn unicode_identifiers() {
+    let (tokens, errors) = scan("var ñame = \"é\";");
+    assert!(errors.is_empty());
+    assert_eq!(raw(&tokens), vec!["var", "ñame", "=", "\"é\"", ";", ""]);
+  }
+
+  #[test]
+  fn escapes() {
+    let (tokens, errors) = scan(r#""a\n\t\r\\\"\$b""#);
+    assert!(errors.is_empty());
+    assert!(tokens[0].ty == TokenType::Literal(Lit::Str("a\n\t\r\\\"$b".to_string())));
+  }
+
+  #[test]
+  fn escape_at_eof_is_an_unterminated_string() {
+    let (_, errors) = scan("print \"abc\\");
+    assert_eq!(messages(&errors), vec!["[Line: 1:7] Error: Unterminated string."]);
+  }
+
+  #[test]
+  fn unknown_escape_is_reported_at_the_backslash() {
+    let (tokens, errors) = scan("\"a\\qb\" x");
+    assert_eq!(messages(&errors), vec!["[Line: 1:3] Error: Unknown escape sequence '\\q'."]);
+    // the string still ends at its closing quote
+    assert_eq!(raw(&tokens), vec!["x", ""]);
+  }
+
+  #[test]
+  fn tokens_after_a_block_comment_on_the_same_line() {
+    let (tokens, errors) = scan("/* comment */ print 1;");
+    assert!(errors.is_empty());
+    assert_eq!(raw(&tokens), vec!["print", "1", ";", ""]);
+    assert_eq!(tokens[0].pos, Pos { line: 1, ch: 15, idx: 14 });
+  }
+
+  #[test]
+  fn block_comments_nest() {
+    let (tokens, errors) = scan("/* a /* b */ c */ x");
+    assert!(errors.is_empty());
+    assert_eq!(raw(&tokens), vec!["x", ""]);
+  }
+
+  #[test]
+  fn unterminated_block_comment_is_reported_at_its_start() {
+    let (_, errors) = scan("x\n  /* a /* b */");
+    assert_eq!(messages(&errors), vec!["[Line: 2:3] Error: Unterminated block comment."]);
+  }
+
+  #[test]
+  fn token_types_describe_themselves() {
+    assert_eq!(TokenType::RightParen.to_string(), "')'");
+    assert_eq!(TokenType::Ident("x".to_string()).to_string(), "identifier");
+    assert_eq!(TokenType::Literal(Lit::Num(1.5)).to_string(), "1.5");
+    assert_eq!(TokenType::Literal(Lit::Str("s".to_string())).to_string(), "\"s\"");
+    assert_eq!(TokenType::EOF.to_string(), "end of input");
+  }
+
+  #[test]
+  fn numeric_separators() {
+    let (tokens, errors) = scan("1_000_000.000_5");
+    assert!(errors.is_empty());
+    assert!(tokens[0].ty == TokenType::Literal(Lit::Num(1_000_000.000_5)));
+  }
+
+  #[test]
+  fn trailing_separator() {
+    let (_, errors) = scan("1_;");
+    assert_eq!(messages(&errors), vec!["[Line: 1:2] Error: Numeric separator '_' must be between two digits."]);
+  }
+
+  #[test]
+  fn doubled_separator() {
+    let (_, errors) = scan("1__2;");
+    assert_eq!(messages(&errors), vec!["[Line: 1:2] Error: Numeric separator '_' must be between two digits."]);
+  }
+
+  #[test]
+  fn separator_before_the_fraction() {
+    let (_, errors) = scan("1_.5;");
+    assert_eq!(messages(&errors), vec!["[Line: 1:2] Error: Numeric separator '_' must be between two digits."]);
+  }
+
+  #[test]
+  fn leading_underscore_is_an_identifier() {
+    let (tokens, errors) = scan("_1");
+    assert!(errors.is_empty());
+    assert!(tokens[0].ty == TokenType::Ident("_1".to_string()));
+  }
+
+  #[test]
+  fn tokens_after_a_bad_character_are_kept() {
+    let (tokens, errors) = scan("print # 1;");
+    assert_eq!(messages(&errors), vec!["[Line: 1:7] Error: Unexpected character: '#'"]);
+    assert_eq!(raw(&tokens), vec!["print", "1", ";", ""]);
+  }
+
+  #[test]
+  fn arrow() {
+    assert!(types("->") == vec![TokenType::Arrow, TokenType::EOF]);
+    assert!(types("- >") == vec![TokenType::Minus, TokenType::Greater, TokenType::EOF]);
+  }
+
+  #[test]
+  fn dots() {
+    use self::TokenType::*;
+    assert!(types(".") == vec![Dot, EOF]);
+    assert!(types("..") == vec![DotDot, EOF]);
+    assert!(types("..=") == vec![DotDotEqual, EOF]);
+    assert!(types("...") == vec![DotDotDot, EOF]);
+    assert!(types(". . .") == vec![Dot, Dot, Dot, EOF]);
+    assert!(types("....") == vec![DotDotDot, Dot, EOF]);
+  }
+
+  #[test]
+  fn decimal_point_stays_in_the_number() {
+    assert!(types("1.5") == vec![TokenType::Literal(Lit::Num(1.5)), TokenType::EOF]);
+    assert!(types("1..5") == vec![TokenType::Literal(Lit::Num(1.0)), TokenType::DotDot, TokenType::Literal(Lit::Num(5.0)), TokenType::EOF]);
+  }
+
+  #[test]
+  fn unterminated_interpolation_is_reported_at_its_start() {
+    let (_, errors) = scan("print \"a ${1 + \n\n 2;");
+    assert_eq!(messages(&errors), vec!["[Line: 1:10] Error: Unterminated string interpolation."]);
+  }
+}
@@ -0,0 +1,17 @@
+use std::hint::black_box;
+
+// The parser and the interpreter recurse on the native stack, and how much one level takes
+// varies wildly, a parenthesized expression in a debug build is over 30KB, a unary minus a
+// fraction of that. So instead of trusting a level count they budget the bytes they use.
+
+// roughly where the top of the stack is right now
+#[inline(never)]
+pub fn position() -> usize {
+  let marker = 0u8;
+  black_box(&marker) as *const u8 as usize
+}
+
+// how much stack was taken since `base`, whichever way the stack grows
+pub fn used_since(base: usize) -> usize {
+  base.abs_diff(position())
+}
@@ -0,0 +1,76 @@
+use std::{
+  env,
+  fs,
+  path::PathBuf,
+  process::{Command, Output},
+};
+
+// writes the script to a temp file and runs rlox on it
+fn rlox(name: &str, args: &[&str], source: &str) -> Output {
+  let path: PathBuf = env::temp_dir().join(format!("jlox-cli-{}-{}.lox", name, std::process::id()));
+  fs::write(&path, source).expect("Couldn't write the script");
+  let output = Command::new(env!("CARGO_BIN_EXE_jlox"))
+    .args(args)
+    .arg(&path)
+    .output()
+    .expect("Couldn't run rlox");
+  fs::remove_file(&path).ok();
+  output
+}
+
+fn stdout(output: &Output) -> String {
+  String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn exit_from_result_uses_the_last_expression() {
+  let output = rlox("exit", &["--exit-from-result"], "var answer = 40;\nanswer + 2;\n");
+  assert_eq!(output.status.code(), Some(42));
+}
+
+#[test]
+fn profile_counts_top_level_expressions() {
+  let output = rlox("profile", &["--profile"], "1 + 2;\n3;\n");
+  assert_eq!(output.status.code(), Some(0));
+  let profile = String::from_utf8_lossy(&output.stderr);
+  let counted = profile.lines().any(|line| line.split_whitespace().eq(["Stmt::Expression", "2"]));
+  assert!(counted, "{}", profile);
+}
+
+#[test]
+fn flags_combine() {
+  let output = rlox("combine", &["--release", "--exit-from-result"], "assert false;\n7;\n");
+  assert_eq!(output.status.code(), Some(7), "{}", stdout(&output));
+}
+
+#[test]
+fn unknown_flags_print_usage() {
+  let output = rlox("usage", &["--frobnicate"], "");
+  assert_eq!(output.status.code(), Some(1));
+  assert!(stdout(&output).starts_with("Usage: rlox"));
+}
+
+#[test]
+fn main_runs_after_the_top_level() {
+  let output = rlox("main", &["--main"], "fun main() { print \"main\"; }\nprint \"top\";\n");
+  assert_eq!(output.status.code(), Some(0));
+  assert_eq!(stdout(&output), "top\nmain\n");
+}
+
+#[test]
+fn tests_report_failures() {
+  let source = "@test fun passes() { assert true; }\n@test fun fails() { assert false; }\n";
+  let output = rlox("tests", &["--test"], source);
+  assert_eq!(output.status.code(), Some(1));
+  let stdout = stdout(&output);
+  assert!(stdout.contains("test passes ... ok\n"), "{}", stdout);
+  assert!(stdout.contains("test fails ... FAILED\n"), "{}", stdout);
+  assert!(stdout.ends_with("test result: FAILED. 1 passed; 1 failed\n"), "{}", stdout);
+}
+
+#[test]
+fn top_level_errors_stop_before_main() {
+  let output = rlox("main-error", &["--main"], "fun main() { print \"main\"; }\nprint missing;\n");
+  assert_eq!(output.status.code(), Some(1));
+  assert!(!stdout(&output).contains("main\n"));
+}